@@ -1,11 +1,12 @@
-pub const HELP: &str = "\
-Usage: how_to_rust [command]
-  Each command runs an example file named after the command.
-  (This CLI uses the `pattern_match_switch_statement` example.)
-
-Commands:
-  console_log
-  get_home_dir
-  import_function
-  oop
+/// A backslash right after the opening quote skips the first newline, so the text can start on
+/// its own line. Every other newline and leading space is kept.
+pub const POEM: &str = "\
+Roses are red,
+  violets are blue,
+a string can span lines
+  and keep its indents too.
 ";
+
+pub fn run() {
+    println!("This is how you print a multiline string:\n{}", POEM);
+}
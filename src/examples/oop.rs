@@ -1,183 +1,2617 @@
+use std::any::Any;
 use std::collections::HashMap;
-use std::ops::{Index, Range, Add};
-use std::rc::Rc;
-use std::cell::RefCell;
-use crate::examples::multi_line_string::HELP;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::iter::Peekable;
+use std::slice::Iter;
 
+pub mod completion;
+pub mod config;
+pub mod context;
+mod definition;
+mod error;
+mod external;
+mod flags;
+mod help;
+pub mod json;
+pub mod macros;
+mod man;
+mod markdown;
+pub mod messages;
+pub mod prompt;
+mod response;
+pub mod style;
+#[cfg(test)]
+mod testing;
+pub mod value;
+
+pub use context::Context;
+pub use definition::DefinitionError;
+pub use error::{ActionError, ActionResult, ParseError};
+use crate::examples::logger::{BasicLogger, Logger};
+use completion::Shell;
+use messages::Catalog;
+use config::{Config, ValueSource};
+use flags::FlagSpec;
+use prompt::Prompter;
+use style::{ColorChoice, Style};
+use value::{BoolParser, EnumParser, IntegerParser, Matches, StringParser, ValueParser, Validator};
+
+/// A command in a tree of commands. `Program` is a handle: clones share the same command, so a
+/// change made through any handle is seen from the root and from every other handle.
 #[derive(Clone)]
-struct Program {
+pub struct Program(Link);
+
+/// The command a `Program` handle points to. Children are owned by their parent, and point back
+/// to it with a weak link so the tree can be dropped.
+struct ProgramData {
     name: String,
+    aliases: Vec<String>,
+    hidden: bool,
+    external_subcommands: bool,
     version: Option<String>,
     description: Option<String>,
-    parent: Option<Link>,
-    children: HashMap<String, Link>,
+    parent: Option<WeakLink>,
+    children: HashMap<String, Program>,
     arguments: OptionalProgramArguments,
     options: OptionalProgramOptions,
-    action: fn(OptionalProgramArguments, OptionalProgramOptions)
+    config_file: Option<PathBuf>,
+    groups: Vec<(String, GroupRule)>,
+    output: Option<Output>,
+    stdout: Option<Stream>,
+    stderr: Option<Stream>,
+    prompter: Option<Rc<RefCell<Prompter>>>,
+    no_prompts: bool,
+    response_files: bool,
+    messages: Option<Rc<Catalog>>,
+    color: ColorChoice,
+    color_flag: bool,
+    pre_actions: Vec<Action>,
+    post_actions: Vec<Action>,
+    action: Option<Action>
 }
 
-type Link = Rc<RefCell<Program>>;
-type OptionalProgramArguments = Option<Vec<ProgramArgument>>;
-type OptionalProgramOptions = Option<Vec<ProgramOption>>;
+type Link = Rc<RefCell<ProgramData>>;
+type WeakLink = Weak<RefCell<ProgramData>>;
+type Action = Rc<RefCell<dyn FnMut(&mut Context) -> Result<(), ActionError>>>;
+type Output = Rc<RefCell<dyn Logger>>;
+type Stream = Rc<RefCell<dyn Write>>;
+pub type OptionalProgramArguments = Option<Vec<ProgramArgument>>;
+pub type OptionalProgramOptions = Option<Vec<ProgramOption>>;
 
 impl Program {
-    fn new(name: String) -> Self {
-        Self {
+    pub fn new(name: String) -> Self {
+        Program(Rc::new(RefCell::new(ProgramData {
             name,
+            aliases: Vec::new(),
+            hidden: false,
+            external_subcommands: false,
             version: None,
             description: None,
             parent: None,
             children: HashMap::new(),
             arguments: None,
             options: None,
-            action: |arguments: OptionalProgramArguments, options: OptionalProgramOptions| { println!("Command not implemented"); }
+            config_file: None,
+            groups: Vec::new(),
+            output: None,
+            stdout: None,
+            stderr: None,
+            prompter: None,
+            no_prompts: false,
+            response_files: false,
+            messages: None,
+            color: ColorChoice::Auto,
+            color_flag: false,
+            pre_actions: Vec::new(),
+            post_actions: Vec::new(),
+            action: None
+        })))
+    }
+
+    fn borrow(&self) -> Ref<'_, ProgramData> {
+        self.0.borrow()
+    }
+
+    fn borrow_mut(&self) -> RefMut<'_, ProgramData> {
+        self.0.borrow_mut()
+    }
+
+    pub fn name(&self) -> String {
+        self.borrow().name.clone()
+    }
+
+    /// Sets what runs when this program is the command given. The action can capture state and
+    /// is called with a `Context`; an `Err` it returns ends the program with the error's code.
+    pub fn action(self, action: impl FnMut(&mut Context) -> ActionResult + 'static) -> Program {
+        self.borrow_mut().action = Some(Rc::new(RefCell::new(action)));
+        self
+    }
+
+    /// Adds a hook that runs before the action of this program or any subcommand below it. Hooks
+    /// can change the `Context` the action gets; an `Err` stops the action from running. Hooks
+    /// of ancestors run first.
+    #[allow(dead_code)]
+    pub fn pre_action(self, hook: impl FnMut(&mut Context) -> ActionResult + 'static) -> Program {
+        self.borrow_mut().pre_actions.push(Rc::new(RefCell::new(hook)));
+        self
+    }
+
+    /// Adds a hook that runs after the action of this program or any subcommand below it, if the
+    /// action succeeded. Hooks of ancestors run last, so a hook added next to a `pre_action` on
+    /// the root wraps everything else.
+    #[allow(dead_code)]
+    pub fn post_action(self, hook: impl FnMut(&mut Context) -> ActionResult + 'static) -> Program {
+        self.borrow_mut().post_actions.push(Rc::new(RefCell::new(hook)));
+        self
+    }
+
+    /// Creates a subcommand and returns it, so it can be configured in the same chain:
+    /// `program.command(name).description(..).action(..)`.
+    pub fn command(&self, name: String) -> Program {
+        let child = Program::new(name);
+        self.adopt(&child);
+        child
+    }
+
+    /// Adds an already configured program as a subcommand and returns `self`, so several can be
+    /// added in one chain. A program that already is a subcommand elsewhere is moved here.
+    ///
+    /// Panics if `child` is this program or one of its ancestors, as the tree would be a cycle, or
+    /// if it has an option with the long flag `color` in a tree whose root has `color_flag`.
+    pub fn add_command(self, child: Program) -> Program {
+        self.adopt(&child);
+        self
+    }
+
+    /// Links `child` into this program's children, replacing any child with the same name, and
+    /// unlinks it from the children of its previous parent.
+    fn adopt(&self, child: &Program) {
+        let mut ancestor = Some(self.clone());
+        while let Some(program) = ancestor {
+            if Rc::ptr_eq(&program.0, &child.0) {
+                panic!("Command '{}' cannot be added below itself", child.name());
+            }
+            ancestor = program.parent();
         }
+        if let Some(parent) = child.parent() {
+            parent.borrow_mut().children.retain(|_, other| !Rc::ptr_eq(&other.0, &child.0));
+        }
+        child.borrow_mut().parent = Some(Rc::downgrade(&self.0));
+        let name = child.name();
+        self.borrow_mut().children.insert(name, child.clone());
+        child.reject_color_clash();
     }
 
-    fn action(mut self, func: fn(OptionalProgramArguments, OptionalProgramOptions)) {
-        self.action = func;
+    /// The program this one is a subcommand of, if it is not the root.
+    pub fn parent(&self) -> Option<Program> {
+        let parent = self.borrow().parent.as_ref()?.upgrade()?;
+        Some(Program(parent))
     }
 
-    fn command(mut self, name: String) -> (Program, Program) {
-        let mut child = Program::new(name);
-        let mut child_link = Rc::new(RefCell::new(child.clone()));
-        self.children.insert(child.name.clone(), child_link);
-        let parent_link = Rc::new(RefCell::new(self.clone()));
-        child.parent = Some(parent_link);
-        return (self, child);
+    /// The names of the programs from the root down to this one.
+    pub fn path(&self) -> Vec<String> {
+        let mut path = match self.parent() {
+            Some(parent) => parent.path(),
+            None => Vec::new()
+        };
+        path.push(self.name());
+        path
     }
 
-    fn description(mut self, description: String) -> Program {
-        self.description = Some(description);
-        return self;
+    /// Adds another name the program can be run by, e.g. `ls` for `list`.
+    pub fn alias(self, alias: String) -> Program {
+        self.borrow_mut().aliases.push(alias);
+        self
     }
 
-    fn parse(&self) {
-        let args: Vec<String> = std::env::args().collect();
-        let mut first_command = "";
+    /// Leaves the program out of help and completions. It can still be run.
+    pub fn hidden(self) -> Program {
+        self.borrow_mut().hidden = true;
+        self
+    }
 
-        if args.len() < 2 {
-            return println!("{}", HELP);
-        } else {
-            first_command = &args[1];
+    /// Runs an unknown subcommand `foo` as the executable `<path>-foo` from `$PATH`, the way git
+    /// runs `git-foo`, if there is one. It gets the arguments around `foo`, the options before it
+    /// first, and this process's stdin. It writes to the `stdout` and `stderr` writers if they are
+    /// set and to this process's otherwise, and a non-zero exit code is passed on. Only programs
+    /// without positional arguments look for external subcommands, so a value is never taken for
+    /// one.
+    pub fn external_subcommands(self) -> Program {
+        self.borrow_mut().external_subcommands = true;
+        self
+    }
+
+    /// Sets the version printed by `-V` or `--version` on this program.
+    pub fn version(self, version: String) -> Program {
+        self.borrow_mut().version = Some(version);
+        self
+    }
+
+    pub fn description(self, description: String) -> Program {
+        self.borrow_mut().description = Some(description);
+        self
+    }
+
+    /// Adds a positional argument. Arguments follow the commander.js grammar: `<required>`,
+    /// `[optional]`, and a trailing `<many...>` or `[many...]` that collects the rest.
+    ///
+    /// Panics if the argument cannot come after the ones already added: nothing may follow a
+    /// variadic argument, and a required argument may not follow an optional one.
+    pub fn argument(self, argument: ProgramArgument) -> Program {
+        let mut program = self.borrow_mut();
+        let arguments = program.arguments.get_or_insert_with(Vec::new);
+        if let Some(last) = arguments.last() {
+            if last.is_variadic() {
+                panic!("Argument '{}' cannot follow the variadic argument '{}'", argument.name, last.name);
+            }
+            if argument.is_required() && !last.is_required() {
+                panic!("Required argument '{}' cannot follow the optional argument '{}'", argument.name, last.name);
+            }
         }
+        arguments.push(argument);
+        drop(program);
+        self
+    }
 
-        if is_root(self) {
-            let command = get_child(self, String::from(first_command));
-            match command {
-                Some(program) => println!("todo"), // TODO: invoke the action eventually
-                None => return println!("{}", HELP)
-            };
+    /// Adds an option, such as `-o, --output <file>`, to this program. An option that joins a
+    /// group with `ProgramOption::group` needs the group declared with `group` on this program or
+    /// an ancestor by the time the program is parsed, or parsing fails with `UndeclaredGroup`
+    /// whatever the arguments are. So the command can be built before it is added to the parent
+    /// that declares the group.
+    ///
+    /// Panics if the option's long flag is `color`, as in `--color` or `--no-color`, and the root
+    /// of the tree has `color_flag`, which takes that flag.
+    pub fn option(self, option: ProgramOption) -> Program {
+        self.borrow_mut().options.get_or_insert_with(Vec::new).push(option);
+        self.reject_color_clash();
+        self
+    }
+
+    /// Declares a group of options, which options join with `ProgramOption::group`, and how many
+    /// of them must be given. Options of a program's ancestors can join its groups too.
+    pub fn group(self, name: String, rule: GroupRule) -> Program {
+        self.borrow_mut().groups.push((name, rule));
+        self
+    }
+
+    /// The rule of the group `name`, declared on this program or the closest ancestor that has it.
+    fn group_rule(&self, name: &str) -> Option<GroupRule> {
+        let rule = self.borrow().groups.iter().find(|(group, _)| group == name).map(|(_, rule)| *rule);
+        rule.or_else(|| self.parent()?.group_rule(name))
+    }
+
+    /// The groups declared on this program and its ancestors, closest first.
+    fn all_groups(&self) -> Vec<(String, GroupRule)> {
+        let mut groups = self.borrow().groups.clone();
+        if let Some(parent) = self.parent() {
+            for (name, rule) in parent.all_groups() {
+                if !groups.iter().any(|(group, _)| *group == name) {
+                    groups.push((name, rule));
+                }
+            }
+        }
+        groups
+    }
+
+    /// Sets a config file that options of this program and its subcommands fall back to when
+    /// they are not given on the command line or in their environment variable. The file does
+    /// not have to exist.
+    pub fn config_file(self, path: PathBuf) -> Program {
+        self.borrow_mut().config_file = Some(path);
+        self
+    }
+
+    /// Sets where actions of this program and its subcommands print to with `Context::print`.
+    /// Without one they print to stdout.
+    #[allow(dead_code)]
+    pub fn output(self, logger: impl Logger + 'static) -> Program {
+        self.borrow_mut().output = Some(Rc::new(RefCell::new(logger)));
+        self
+    }
+
+    /// Sets where this program and its subcommands print help and versions, and where actions
+    /// print with `Context::print` when there is no `output`. Without one it is stdout.
+    #[allow(dead_code)]
+    pub fn stdout(self, writer: impl Write + 'static) -> Program {
+        self.borrow_mut().stdout = Some(Rc::new(RefCell::new(writer)));
+        self
+    }
+
+    /// Sets where `parse` asks for missing arguments and `run_from` reports errors. Without one
+    /// it is stderr.
+    #[allow(dead_code)]
+    pub fn stderr(self, writer: impl Write + 'static) -> Program {
+        self.borrow_mut().stderr = Some(Rc::new(RefCell::new(writer)));
+        self
+    }
+
+    /// Sets how this program and its subcommands ask for missing required arguments. `parse`
+    /// asks on the terminal when there is none.
+    #[allow(dead_code)]
+    pub fn prompter(self, prompter: Prompter) -> Program {
+        self.borrow_mut().prompter = Some(Rc::new(RefCell::new(prompter)));
+        self
+    }
+
+    /// Never asks for missing arguments on this program or its subcommands, so scripts fail
+    /// instead of waiting for input.
+    pub fn no_prompts(self) -> Program {
+        self.borrow_mut().no_prompts = true;
+        self
+    }
+
+    /// Sets the messages help, errors and prompts of this program and its subcommands are shown
+    /// in. Without a catalog they are in English.
+    #[allow(dead_code)]
+    pub fn messages(self, catalog: Catalog) -> Program {
+        self.borrow_mut().messages = Some(Rc::new(catalog));
+        self
+    }
+
+    /// The catalog set with `messages` on this program or the closest ancestor, or English.
+    fn catalog(&self) -> Rc<Catalog> {
+        if let Some(catalog) = &self.borrow().messages {
+            return catalog.clone();
+        }
+        match self.parent() {
+            Some(parent) => parent.catalog(),
+            None => Rc::new(Catalog::english())
+        }
+    }
+
+    /// Sets when help and errors are styled with colors and bold text. By default they are when
+    /// written to a terminal and `NO_COLOR` is not set.
+    pub fn color(self, choice: ColorChoice) -> Program {
+        self.borrow_mut().color = choice;
+        self
+    }
+
+    /// Accepts `--color <when>`, with `auto`, `always` or `never`, as an option of this program
+    /// that its subcommands inherit, to override `color`.
+    ///
+    /// Panics if this program or a command below it has an option with the long flag `color`.
+    pub fn color_flag(self) -> Program {
+        self.borrow_mut().color_flag = true;
+        self.reject_color_clash();
+        self
+    }
+
+    /// Whether the root of this program's tree accepts `--color`.
+    fn accepts_color_flag(&self) -> bool {
+        match self.parent() {
+            Some(parent) => parent.accepts_color_flag(),
+            None => self.borrow().color_flag
+        }
+    }
+
+    /// Panics if the root accepts `--color` and an option of this program or a command below it
+    /// has the long flag `color` too, as `--color` would be read as that option in its command.
+    fn reject_color_clash(&self) {
+        if !self.accepts_color_flag() {
+            return;
+        }
+        let program = self.borrow();
+        if let Some(option) = program.options.iter().flatten().find(|option| option.spec.long.as_deref() == Some("color")) {
+            panic!("Option '{}' cannot be added, the program's color_flag takes --color", option.flags);
+        }
+        program.children.values().for_each(Program::reject_color_clash);
+    }
+
+    /// Replaces each `@path` argument with the arguments in that file before parsing, for
+    /// command lines too long to type. Words in the file are split at whitespace outside quotes
+    /// and can name other files, unless quoted.
+    pub fn response_files(self) -> Program {
+        self.borrow_mut().response_files = true;
+        self
+    }
+
+    /// Parses the process arguments and runs the matched command's action. Returns the typed
+    /// values of that command. Required arguments that are missing are asked for when stdin is
+    /// a terminal, and help is wrapped to the terminal's width.
+    #[allow(dead_code)]
+    pub fn parse(&self) -> Result<Matches, ParseError> {
+        let color = Rc::new(Cell::new(self.borrow().color));
+        self.parse_strings(utf8_args(std::env::args_os().skip(1))?, &color, true)
+    }
+
+    /// Parses `args`, which do not start with the program's own name, like `parse` does the
+    /// process arguments. Nothing is printed for an error and the process never exits, so the
+    /// caller decides what happens next. Nothing depends on the terminal either: missing
+    /// arguments are only asked for with a `prompter`, and help is 80 columns wide. `stderr`,
+    /// `color`, `color_flag` and `response_files` apply to the whole command line, so only the
+    /// program this is called on decides them.
+    #[allow(dead_code)]
+    pub fn parse_from<I, T>(&self, args: I) -> Result<Matches, ParseError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>
+    {
+        let color = Rc::new(Cell::new(self.borrow().color));
+        self.parse_strings(utf8_args(args)?, &color, false)
+    }
+
+    /// Runs `parse` and returns the code to exit the process with, as `run_from` does.
+    pub fn run(&self) -> i32 {
+        self.run_strings(utf8_args(std::env::args_os().skip(1)), true)
+    }
+
+    /// Runs `parse_from` and returns the code to exit the process with: 0 on success, or the
+    /// error's code after writing `error: <message>` to stderr, styled as `color` or `--color`
+    /// says.
+    #[allow(dead_code)]
+    pub fn run_from<I, T>(&self, args: I) -> i32
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>
+    {
+        self.run_strings(utf8_args(args), false)
+    }
+
+    /// Runs `parse_strings` for `run` and `run_from`, reporting an error in `args` too.
+    fn run_strings(&self, args: Result<Vec<String>, ParseError>, terminal: bool) -> i32 {
+        let color = Rc::new(Cell::new(self.borrow().color));
+        let error = match args.and_then(|args| self.parse_strings(args, &color, terminal)) {
+            Ok(_) => return 0,
+            Err(error) => error
         };
+        let stderr = self.borrow().stderr.clone();
+        let style = color.get().style(stderr.is_none() && std::io::stderr().is_terminal());
+        let messages = self.catalog();
+        let message = error_line(&messages, style, &error.styled_message(&messages, style));
+        match stderr {
+            Some(stderr) => {
+                let _ = writeln!(stderr.borrow_mut(), "{}", message);
+            }
+            None => eprintln!("{}", message)
+        }
+        error.exit_code()
+    }
 
-        println!("did I make it");
+    /// Parses arguments that are known to be UTF-8 for `parse` and `parse_from`. `color` is set
+    /// to the choice given with `--color` as soon as it is read, so errors after it can be
+    /// styled. Only with `terminal` are missing arguments asked for on the terminal and help
+    /// wrapped to its width.
+    fn parse_strings(&self, args: Vec<String>, color: &Rc<Cell<ColorChoice>>, terminal: bool) -> Result<Matches, ParseError> {
+        self.check_groups()?;
+        let args = self.expand_response_files(&args)?;
+        let stderr = self.borrow().stderr.clone();
+        let (prompter, width) = match terminal {
+            true => {
+                let prompter = match stderr.clone() {
+                    Some(stderr) => Prompter::terminal().output(SharedWriter(stderr)),
+                    None => Prompter::terminal()
+                };
+                (Some(Rc::new(RefCell::new(prompter))), Some(help::terminal_width()))
+            }
+            false => (None, None)
+        };
+        // `--color` is an option of the root, so it is read and checked wherever its options are.
+        let options = match self.borrow().color_flag {
+            true => {
+                let names = ColorChoice::NAMES.iter().map(|name| String::from(*name)).collect();
+                let description = String::from(self.catalog().get("help.color"));
+                vec![ProgramOption::new(String::from(help::COLOR_FLAGS), description).parser(EnumParser(names))]
+            }
+            false => Vec::new()
+        };
+        let inherited = Inherited { options, stderr, color: color.clone(), prompter, width, ..Inherited::default() };
+        self.dispatch_from(&inherited, &args)
     }
-}
 
-fn is_root(program: &Program) -> bool {
-    program.parent.is_none()
-}
+    /// The help text for this program, as printed by `-h` or `--help`, without styles.
+    #[allow(dead_code)]
+    pub fn help(&self) -> String {
+        let config = self.load_config(&Config::default()).unwrap_or_default();
+        help::render(self, &config, help::terminal_width(), Style::default())
+    }
 
-fn get_child(program: &Program, name: String) -> Option<&Link> {
-    if !program.children.contains_key(name.as_str()) {
-        println!("Command not found");
-        return None;
+    /// A script for `shell` that completes this program's subcommands, flags and the values of
+    /// enum-valued options and arguments.
+    pub fn completions(&self, shell: Shell) -> String {
+        completion::render(self, shell)
     }
-    return program.children.get(name.as_str());
-}
 
-#[derive(Clone)]
-struct ProgramArgument {
-    name: String,
-    description: String,
-    default: String
-}
+    /// A man(7) page for this program and one for each visible subcommand below it, as
+    /// `(file name, page)` pairs such as `("git-remote.1", ...)`.
+    pub fn man_pages(&self) -> Vec<(String, String)> {
+        man::render(self)
+    }
 
-#[derive(Clone)]
-struct ProgramOption {
-    flags: String,
-    description: String,
-    default: String,
-    argument: ProgramArgument
-}
+    /// A JSON description of this program and every subcommand below it: names, arguments,
+    /// options, defaults, groups and the relations between options. Actions, hooks, validators
+    /// and custom parsers are code and are left out.
+    pub fn to_json(&self) -> String {
+        definition::to_json(self).to_string()
+    }
 
-impl ProgramArgument {
-    fn is_required(&self) -> bool {
-        if self.name.len() > 0 {
-            return self.name.index(Range{start: 0, end: 1}) == "<";
+    /// Builds a program from a description written by `to_json`. It has no actions; add them to
+    /// its commands before running it.
+    #[allow(dead_code)]
+    pub fn from_json(text: &str) -> Result<Program, DefinitionError> {
+        let json = json::Json::parse(text).map_err(DefinitionError::Syntax)?;
+        definition::from_json(&json)
+    }
+
+    /// A Markdown reference for this program and each visible subcommand below it, with an
+    /// anchor, usage and tables of arguments, options and commands per command.
+    pub fn markdown(&self) -> String {
+        markdown::render(self)
+    }
+
+    #[cfg(test)]
+    fn dispatch(&self, args: &[String]) -> Result<Matches, ParseError> {
+        self.check_groups()?;
+        self.dispatch_from(&Inherited::default(), &self.expand_response_files(args)?)
+    }
+
+    fn expand_response_files(&self, args: &[String]) -> Result<Vec<String>, ParseError> {
+        if self.borrow().response_files {
+            response::expand(args)
+        } else {
+            Ok(args.to_vec())
         }
-        return false;
     }
-    fn print(&self) {
-        println!("{}, {}, {}", self.name, self.description, self.default);
+
+    /// Walks down the `children` tree while the next argument that is not an option names a
+    /// subcommand, then hands the remaining arguments to the deepest program that matched.
+    /// Options given before a subcommand name are handed down with the rest, to be parsed as
+    /// options inherited from this program.
+    fn dispatch_from(&self, inherited: &Inherited, args: &[String]) -> Result<Matches, ParseError> {
+        let config = self.load_config(&inherited.config)?;
+        let index = self.command_index(args, &inherited.options);
+        // Arguments after a subcommand's name are read by that subcommand, with its options.
+        let own = match args.get(index) {
+            Some(name) if get_child(self, name.clone()).is_ok() => &args[..index],
+            _ => args
+        };
+        for (flag, value) in self.flags_given(own, inherited) {
+            if let (true, Some(Ok(choice))) = (flag == "--color", value.map(|value| value.parse())) {
+                inherited.color.set(choice);
+            }
+        }
+
+        if let Some(first) = args.first() {
+            if first == "help" && self.borrow().has_help_command() {
+                let target = self.find_command(&args[1..])?;
+                let style = self.stdout_style(inherited);
+                self.print(inherited, &help::render(&target, &config, inherited.width.unwrap_or(help::WIDTH), style));
+                return Ok(Matches::default());
+            }
+        }
+        if let Some(name) = args.get(index) {
+            match get_child(self, name.clone()) {
+                Ok(child) => {
+                    let program = self.borrow();
+                    let inherited = Inherited {
+                        config,
+                        options: program.options.iter().flatten().chain(&inherited.options).cloned().collect(),
+                        output: program.output.clone().or_else(|| inherited.output.clone()),
+                        stdout: program.stdout.clone().or_else(|| inherited.stdout.clone()),
+                        stderr: inherited.stderr.clone(),
+                        color: inherited.color.clone(),
+                        width: inherited.width,
+                        pre_actions: inherited.pre_actions.iter().chain(&program.pre_actions).cloned().collect(),
+                        post_actions: program.post_actions.iter().chain(&inherited.post_actions).cloned().collect(),
+                        prompter: program.prompter.clone().or_else(|| inherited.prompter.clone()),
+                        no_prompts: program.no_prompts || inherited.no_prompts,
+                        search_path: inherited.search_path.clone(),
+                        env: inherited.env.clone()
+                    };
+                    drop(program);
+                    let rest: Vec<String> = args[..index].iter().chain(&args[index + 1..]).cloned().collect();
+                    return child.dispatch_from(&inherited, &rest);
+                }
+                Err(error) => {
+                    if let Some(external) = self.find_external(name, inherited) {
+                        let rest: Vec<String> = args[..index].iter().chain(&args[index + 1..]).cloned().collect();
+                        return external::run(&external, &rest, self.stdout_for(inherited), inherited.stderr.clone())
+                            .map(|_| Matches::default());
+                    }
+                    if self.expects_command() {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        let flags = self.flags_given(args, inherited);
+        if let Some(version) = &self.borrow().version {
+            if wants_version(&flags) {
+                self.print(inherited, version);
+                return Ok(Matches::default());
+            }
+        }
+
+        // A program that only groups subcommands shows its help when none is given.
+        if wants_help(&flags) || (args.is_empty() && self.expects_command()) {
+            let width = inherited.width.unwrap_or(help::WIDTH);
+            self.print(inherited, &help::render(self, &config, width, self.stdout_style(inherited)));
+            return Ok(Matches::default());
+        }
+
+        let Parsed { arguments, options, globals } = self.parse_values(args, &config, inherited)?;
+        let program = self.borrow();
+        let action = match &program.action {
+            Some(action) => action.clone(),
+            None if !program.children.is_empty() => return Err(ParseError::MissingCommand { command: program.name.clone() }),
+            None => return Err(ParseError::NotImplemented { command: program.name.clone() })
+        };
+        let output: Output = match (program.output.clone().or_else(|| inherited.output.clone()), self.stdout_for(inherited)) {
+            (Some(output), _) => output,
+            (None, Some(stdout)) => Rc::new(RefCell::new(SharedWriter(stdout))),
+            (None, None) => Rc::new(RefCell::new(BasicLogger))
+        };
+        let pre_actions: Vec<Action> = inherited.pre_actions.iter().chain(&program.pre_actions).cloned().collect();
+        let post_actions: Vec<Action> = program.post_actions.iter().chain(&inherited.post_actions).cloned().collect();
+        let command = program.name.clone();
+        drop(program);
+
+        let mut context = Context {
+            path: self.path(),
+            matches: Matches::new(&arguments, &options),
+            globals: Matches::new(&[], &globals),
+            arguments,
+            options,
+            output
+        };
+        for action in pre_actions.iter().chain([&action]).chain(&post_actions) {
+            (action.borrow_mut())(&mut context).map_err(|error| ParseError::ActionFailed { command: command.clone(), error })?;
+        }
+        Ok(context.matches)
     }
-}
 
-pub fn run() {
-    println!("This is how you mimic a `class` in Rustlang!")
-}
+    /// The flags in `args`, read with this program's own and inherited options.
+    fn flags_given(&self, args: &[String], inherited: &Inherited) -> Vec<(String, Option<String>)> {
+        let program = self.borrow();
+        let options: Vec<&ProgramOption> = program.options.iter().flatten().chain(&inherited.options).collect();
+        scan_flags(args, &options)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::cell::Ref;
+    /// The writer set with `Program::stdout` on this program or the closest ancestor.
+    fn stdout_for(&self, inherited: &Inherited) -> Option<Stream> {
+        self.borrow().stdout.clone().or_else(|| inherited.stdout.clone())
+    }
 
-    #[test]
-    fn program_action_is_set() {
-        let mut program = Program::new(String::from("root"));
-        fn help(arguments: OptionalProgramArguments, options: OptionalProgramOptions) {
-            match arguments {
-                Some(x) => println!("{:?}", x.len()),
-                _ => ()
+    /// How to style what is printed to stdout: as the color choice says, where `auto` depends
+    /// on whether stdout is the process's and a terminal.
+    fn stdout_style(&self, inherited: &Inherited) -> Style {
+        inherited.color.get().style(self.stdout_for(inherited).is_none() && std::io::stdout().is_terminal())
+    }
+
+    /// Writes `text` and a newline to `stdout`, or to the process's stdout if there is none.
+    fn print(&self, inherited: &Inherited, text: &str) {
+        match self.stdout_for(inherited) {
+            Some(stdout) => {
+                let _ = writeln!(stdout.borrow_mut(), "{}", text);
+            }
+            None => println!("{}", text)
+        }
+    }
+
+    /// What asks for this program's missing arguments, unless prompts are off here or above.
+    fn prompter_for(&self, inherited: &Inherited) -> Option<Rc<RefCell<Prompter>>> {
+        let program = self.borrow();
+        if program.no_prompts || inherited.no_prompts {
+            return None;
+        }
+        program.prompter.clone().or_else(|| inherited.prompter.clone())
+    }
+
+    /// Checks that the options of this program and every subcommand below it join only groups
+    /// declared on their command or above. It runs before anything is parsed, so the mistake
+    /// shows whichever command the arguments pick.
+    fn check_groups(&self) -> Result<(), ParseError> {
+        let program = self.borrow();
+        for option in program.options.iter().flatten() {
+            if let Some(group) = option.group.as_ref().filter(|group| self.group_rule(group).is_none()) {
+                return Err(ParseError::UndeclaredGroup { option: option.flag(), group: group.clone() });
+            }
+        }
+        program.children.values().try_for_each(Program::check_groups)
+    }
+
+    /// Checks the conflicts, requirements and groups declared on `options`, which have their
+    /// values. An option counts as given when its value did not come from its default.
+    fn check_relations(&self, options: &[ProgramOption]) -> Result<(), ParseError> {
+        let given = |name: &str| options.iter().find(|option| option.name() == name && option.is_given());
+        for option in options.iter().filter(|option| option.is_given()) {
+            if let Some(other) = option.conflicts.iter().find_map(|name| given(name)) {
+                return Err(ParseError::ConflictingOptions { option: option.flag(), other: other.flag() });
             }
-            match options {
-                Some(y) => println!("{:?}", y.len()),
+            if let Some(required) = option.requires.iter().find(|name| given(name).is_none()) {
+                let required = match options.iter().find(|other| other.name() == *required) {
+                    Some(other) => other.flag(),
+                    None => format!("--{}", required)
+                };
+                return Err(ParseError::MissingRequiredOption { option: option.flag(), required });
+            }
+        }
+        for (group, rule) in self.all_groups() {
+            let members: Vec<&ProgramOption> = options.iter().filter(|option| option.group.as_ref() == Some(&group)).collect();
+            // A group declared above may only have members in other commands.
+            if members.is_empty() {
+                continue;
+            }
+            let chosen: Vec<&&ProgramOption> = members.iter().filter(|option| option.is_given()).collect();
+            match (rule, chosen.as_slice()) {
+                (_, []) => {
+                    return Err(ParseError::MissingGroup { group, options: members.iter().map(|option| option.flag()).collect() });
+                }
+                (GroupRule::ExactlyOne, [first, second, ..]) => {
+                    return Err(ParseError::ConflictingOptions { option: first.flag(), other: second.flag() });
+                }
                 _ => ()
             }
         }
-        program.action(help)
+        Ok(())
     }
 
-    #[test]
-    fn program_description_is_set() {
-        let mut program = Program::new(String::from("root"));
-        program = program.description(String::from("root command"));
-        match program.description.clone() {
-            Some(description) => assert_eq!(description, String::from("root command")),
-            None => panic!("Failed to get description")
+    /// The index of the first argument that is not an option or an option's value, which is
+    /// where a subcommand name would be. It is `args.len()` if there is none or `--` comes first.
+    fn command_index(&self, args: &[String], inherited: &[ProgramOption]) -> usize {
+        let program = self.borrow();
+        let options: Vec<&ProgramOption> = program.options.iter().flatten().chain(inherited).collect();
+        let mut index = 0;
+        while let Some(arg) = args.get(index) {
+            if arg == "--" {
+                return args.len();
+            }
+            if arg == "-" || !arg.starts_with('-') {
+                return index;
+            }
+            // Only a value-taking flag that ends the argument can take the next one as its value.
+            let option = match arg.strip_prefix("--") {
+                Some(long) => options.iter().find(|option| option.spec.long.as_deref() == Some(long)),
+                None => arg[1..]
+                    .char_indices()
+                    .find_map(|(at, short)| {
+                        let option = options.iter().find(|option| option.spec.short == Some(short) && option.takes_value())?;
+                        Some((at + short.len_utf8() == arg.len() - 1).then_some(option))
+                    })
+                    .flatten()
+            };
+            index += 1;
+            if let (Some(option), Some(next)) = (option, args.get(index)) {
+                let required = option.spec.placeholder.as_ref().is_some_and(|placeholder| placeholder.required);
+                let optional = option.takes_value() && !next.starts_with('-') && get_child(self, next.clone()).is_err();
+                if required || optional {
+                    index += 1;
+                }
+            }
         }
+        index
     }
 
-    #[test]
-    fn program_command_is_found() {
-        let mut parent = Program::new(String::from("root"));
-        let (parent, child) = parent.command(String::from("command"));
-        let valid_command = get_child(&parent, String::from("command"));
-        match valid_command {
-            Some(cmd) => assert_eq!(*Ref::map(cmd.borrow(), |n| &n.name), String::from("command")),
-            None => panic!("Failed to get the command")
+    /// The executable that runs `name` as an external subcommand, if they are enabled.
+    fn find_external(&self, name: &str, inherited: &Inherited) -> Option<PathBuf> {
+        let program = self.borrow();
+        if !program.external_subcommands || program.arguments.is_some() {
+            return None;
         }
-        let invalid_command = get_child(&parent, String::from("leaf"));
-        match invalid_command {
-            Some(cmd) => panic!("Found command that should be invalid"),
-            None => return
+        drop(program);
+        let path = inherited.search_path.clone().or_else(|| std::env::var_os("PATH"))?;
+        external::find(&format!("{}-{}", self.path().join("-"), name), &path)
+    }
+
+    /// The subcommand reached by following `path` down from this program, as in
+    /// `help lists bench`.
+    fn find_command(&self, path: &[String]) -> Result<Program, ParseError> {
+        let mut command = self.clone();
+        for name in path {
+            command = get_child(&command, name.clone())?;
         }
+        Ok(command)
     }
 
-    #[test]
-    fn parse_prints_help_by_default() {
-        let mut program = Program::new(String::from("root"));
-        let (program, command) = program.command(String::from("command"));
-        program.parse();
+    /// This program's config file, or `inherited` if it has none.
+    fn load_config(&self, inherited: &Config) -> Result<Config, ParseError> {
+        match &self.borrow().config_file {
+            Some(path) => Config::load(path).map_err(|error| ParseError::InvalidConfig {
+                path: error.path,
+                line: error.line,
+                reason: error.reason
+            }),
+            None => Ok(inherited.clone())
+        }
     }
 
-    #[test]
-    fn inequality_symbol_means_required() {
-        let arg = ProgramArgument {
-            name: String::from("<my_required_arg>"),
-            default: String::from("run"),
-            description: String::from("my required argument"),
+    /// Whether the next argument can only be a subcommand name.
+    fn expects_command(&self) -> bool {
+        let program = self.borrow();
+        !program.children.is_empty() && program.action.is_none() && program.arguments.is_none()
+    }
+
+    /// Fills in the values of this program's arguments and options from `args`, then parses each
+    /// of them into its typed value.
+    ///
+    /// Options follow commander.js: short flags can be combined (`-abc`), values can be given as
+    /// `--opt=value` or `--opt value`, `--no-<flag>` turns a flag off and everything after `--` is
+    /// positional. Options missing from `args` fall back to their environment variable, then to
+    /// `config`, then to their default.
+    ///
+    /// The options of this program's ancestors in `inherited` are parsed along with its own
+    /// options and returned separately. Missing required arguments are asked for if there is a
+    /// prompter.
+    fn parse_values(&self, args: &[String], config: &Config, inherited: &Inherited) -> Result<Parsed, ParseError> {
+        let mut arguments = self.borrow().arguments.clone().unwrap_or_default();
+        let own = self.borrow().options.as_ref().map_or(0, Vec::len);
+        let mut options: Vec<ProgramOption> = self.borrow().options.iter().flatten().chain(&inherited.options).cloned().collect();
+        let prompter = self.prompter_for(inherited);
+        let messages = self.catalog();
+        let mut positionals = Vec::new();
+        let mut only_positionals = false;
+
+        let mut iter = args.iter().peekable();
+        while let Some(arg) = iter.next() {
+            if only_positionals || arg == "-" || !arg.starts_with('-') {
+                positionals.push(arg.clone());
+            } else if arg == "--" {
+                only_positionals = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                parse_long_option(options.iter_mut(), long, &mut iter)?;
+            } else {
+                parse_short_options(options.iter_mut().collect(), &arg[1..], &mut iter)?;
+            }
+        }
+
+        let declared = &arguments;
+        let variadic = declared.last().is_some_and(ProgramArgument::is_variadic);
+        if !variadic && positionals.len() > declared.len() {
+            return Err(ParseError::UnexpectedArgument { value: positionals[declared.len()].clone() });
+        }
+        let mut positionals = positionals.into_iter();
+        for argument in arguments.iter_mut() {
+            argument.values = if argument.is_variadic() {
+                positionals.by_ref().collect()
+            } else {
+                positionals.next().into_iter().collect()
+            };
+            if argument.values.is_empty() && argument.is_required() {
+                let answer = prompter.as_ref().and_then(|prompter| prompter.borrow_mut().ask(argument, &messages));
+                argument.values = match answer {
+                    Some(answer) if argument.is_variadic() => answer.split_whitespace().map(String::from).collect(),
+                    Some(answer) => vec![answer],
+                    None => return Err(ParseError::MissingArgument { name: argument.name.clone() })
+                };
+            }
+            argument.resolve("argument", &argument.key(), &messages)?;
+        }
+        for option in options.iter_mut() {
+            option.source = if !option.values.is_empty() {
+                ValueSource::CommandLine
+            } else {
+                match option.fallback(config, inherited.env.as_ref()) {
+                    Some((value, source)) => {
+                        option.values = vec![value];
+                        source
+                    }
+                    None => ValueSource::Default
+                }
+            };
+            option.resolve(&messages)?;
+        }
+
+        self.check_relations(&options)?;
+
+        let globals = options.split_off(own);
+        Ok(Parsed { arguments, options, globals })
+    }
+}
+
+/// Handles `--name`, `--name=value`, `--name value` and `--no-name`. Counted flags have no
+/// `--no-` form, as a count cannot be negated.
+fn parse_long_option<'a>(
+    options: impl Iterator<Item = &'a mut ProgramOption>,
+    long: &str,
+    rest: &mut Peekable<Iter<String>>
+) -> Result<(), ParseError> {
+    let (name, inline) = match long.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (long, None)
+    };
+    let negated = name.strip_prefix("no-");
+
+    let mut options: Vec<&mut ProgramOption> = options.collect();
+    if let Some(option) = options.iter_mut().find(|option| option.spec.long.as_deref() == Some(name)) {
+        return option.take_value(&format!("--{}", name), inline.map(String::from), rest);
+    }
+    if let Some(negated) = negated {
+        let negatable = |option: &&mut &mut ProgramOption| {
+            option.spec.long.as_deref() == Some(negated) && !option.takes_value() && option.repeat != Repeat::Count
         };
-        assert!(arg.is_required())
+        if let Some(option) = options.iter_mut().find(negatable) {
+            if inline.is_some() {
+                return Err(ParseError::UnexpectedValue { option: format!("--{}", name) });
+            }
+            option.values.push(String::from("false"));
+            return Ok(());
+        }
+    }
+    let longs: Vec<String> = options.iter().filter_map(|option| option.spec.long.as_ref()).map(|long| format!("--{}", long)).collect();
+    Err(ParseError::UnknownOption {
+        name: format!("--{}", name),
+        suggestion: error::suggest(&format!("--{}", name), longs.iter().map(String::as_str))
+    })
+}
+
+/// Handles a cluster of short flags such as `-abc`. A flag that takes a value consumes the rest of
+/// the cluster (`-ofile`) or, if the cluster ends there, the next argument.
+fn parse_short_options(
+    mut options: Vec<&mut ProgramOption>,
+    cluster: &str,
+    rest: &mut Peekable<Iter<String>>
+) -> Result<(), ParseError> {
+    for (index, short) in cluster.char_indices() {
+        let Some(option) = options.iter_mut().find(|option| option.spec.short == Some(short)) else {
+            return Err(unknown_short(&options, cluster, short));
+        };
+        if option.takes_value() {
+            let attached = &cluster[index + short.len_utf8()..];
+            let inline = if attached.is_empty() { None } else { Some(String::from(attached)) };
+            return option.take_value(&format!("-{}", short), inline, rest);
+        }
+        option.values.push(String::from("true"));
+    }
+    Ok(())
+}
+
+/// The error for the unknown flag `short` in `cluster`. A cluster that was meant as a long flag
+/// with a single dash (`-verbose`) is reported whole with the long flag it is close to; otherwise
+/// the flag is, with the short flag that only differs in case (`-V` for `-v`).
+fn unknown_short(options: &[&mut ProgramOption], cluster: &str, short: char) -> ParseError {
+    let longs: Vec<String> = options.iter().filter_map(|option| option.spec.long.as_ref()).map(|long| format!("--{}", long)).collect();
+    if cluster.chars().count() > 1 {
+        if let Some(long) = error::suggest(&format!("--{}", cluster), longs.iter().map(String::as_str)) {
+            return ParseError::UnknownOption { name: format!("-{}", cluster), suggestion: Some(long) };
+        }
+    }
+    let suggestion = options
+        .iter()
+        .filter_map(|option| option.spec.short)
+        .find(|other| *other != short && other.to_lowercase().eq(short.to_lowercase()))
+        .map(|other| format!("-{}", other));
+    ParseError::UnknownOption { name: format!("-{}", short), suggestion }
+}
+
+/// Arguments as `String`s, or an error for the first that is not valid UTF-8.
+fn utf8_args<I, T>(args: I) -> Result<Vec<String>, ParseError>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>
+{
+    args.into_iter()
+        .map(|arg| arg.into().into_string().map_err(|arg| ParseError::InvalidEncoding { value: arg.to_string_lossy().into_owned() }))
+        .collect()
+}
+
+/// `message` in the `error.prefix` message, with the label around it styled as an error.
+fn error_line(messages: &Catalog, style: Style, message: &str) -> String {
+    let prefix = messages.get("error.prefix");
+    let (label, rest) = prefix.split_once("{message}").unwrap_or((prefix, ""));
+    let space = &label[label.trim_end().len()..];
+    format!("{}{}{}{}", style.error(label.trim_end()), space, message, style.error(rest))
+}
+
+/// The flags given before any `--`, each with the value it took, read the way `parse_values`
+/// reads them: the value of an option is not a flag, so in `-m -h` the `-h` is `-m`'s value.
+/// The flags of a cluster such as `-vh` are listed one by one.
+fn scan_flags(args: &[String], options: &[&ProgramOption]) -> Vec<(String, Option<String>)> {
+    let mut flags = Vec::new();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "-" || !arg.starts_with('-') {
+            continue;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(String::from(value))),
+                None => (long, None)
+            };
+            let option = options.iter().find(|option| option.spec.long.as_deref() == Some(name));
+            let value = inline.or_else(|| option.and_then(|option| iter.next_if(|next| option.takes_as_value(next))).cloned());
+            flags.push((format!("--{}", name), value));
+            continue;
+        }
+        for (index, short) in arg[1..].char_indices() {
+            match options.iter().find(|option| option.spec.short == Some(short) && option.takes_value()) {
+                Some(option) => {
+                    let attached = &arg[1 + index + short.len_utf8()..];
+                    let value = match attached {
+                        "" => iter.next_if(|next| option.takes_as_value(next)).cloned(),
+                        attached => Some(String::from(attached))
+                    };
+                    flags.push((format!("-{}", short), value));
+                    break;
+                }
+                None => flags.push((format!("-{}", short), None))
+            }
+        }
+    }
+    flags
+}
+
+/// Whether `-h` or `--help` is among the flags `scan_flags` found.
+fn wants_help(flags: &[(String, Option<String>)]) -> bool {
+    flags.iter().any(|(flag, _)| flag == "-h" || flag == "--help")
+}
+
+/// Whether `-V` or `--version` is among the flags `scan_flags` found.
+fn wants_version(flags: &[(String, Option<String>)]) -> bool {
+    flags.iter().any(|(flag, _)| flag == "-V" || flag == "--version")
+}
+
+/// The values `Program::parse_values` read for a command.
+struct Parsed {
+    arguments: Vec<ProgramArgument>,
+    options: Vec<ProgramOption>,
+    /// The command's inherited options.
+    globals: Vec<ProgramOption>
+}
+
+/// What a command hands down to the subcommand it dispatches to.
+#[derive(Clone, Default)]
+struct Inherited {
+    /// The closest ancestor's config file.
+    config: Config,
+    /// The options of every ancestor, closest first.
+    options: Vec<ProgramOption>,
+    output: Option<Output>,
+    stdout: Option<Stream>,
+    /// The writer set with `Program::stderr` on the program `parse` is called on.
+    stderr: Option<Stream>,
+    /// The root's color choice, updated as `--color` is read so errors can be styled with it.
+    color: Rc<Cell<ColorChoice>>,
+    /// The width help is wrapped to, `help::WIDTH` if it is not the terminal's.
+    width: Option<usize>,
+    /// The `pre_action` hooks of every ancestor, root first.
+    pre_actions: Vec<Action>,
+    /// The `post_action` hooks of every ancestor, closest first.
+    post_actions: Vec<Action>,
+    prompter: Option<Rc<RefCell<Prompter>>>,
+    no_prompts: bool,
+    /// Where to look for external subcommands instead of `$PATH`, so tests need not change it.
+    search_path: Option<OsString>,
+    /// The variables options fall back to instead of the process's environment, so tests need
+    /// not change it.
+    env: Option<HashMap<String, String>>
+}
+
+/// A `Stream` that can be handed to a `Prompter`, or to actions as their output.
+struct SharedWriter(Stream);
+
+impl Write for SharedWriter {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+impl Logger for SharedWriter {
+    fn print(&mut self, value: &fmt::Arguments<'_>) {
+        let _ = writeln!(self.0.borrow_mut(), "{}", value);
     }
-}
\ No newline at end of file
+}
+
+impl ProgramData {
+    /// The subcommands that are not hidden, sorted by name.
+    fn visible_children(&self) -> Vec<&Program> {
+        let mut children: Vec<&Program> = self.children.values().filter(|child| !child.borrow().hidden).collect();
+        children.sort_by_key(|child| child.name());
+        children
+    }
+
+    /// Whether `help <command>` is available: a program with subcommands gets a `help` command
+    /// unless it defines its own.
+    fn has_help_command(&self) -> bool {
+        !self.children.is_empty() && !self.children.contains_key("help")
+    }
+}
+
+#[cfg(test)]
+fn is_root(program: &Program) -> bool {
+    program.parent().is_none()
+}
+
+/// Looks up a direct subcommand by name or alias, suggesting the closest visible name if there
+/// is no such command.
+fn get_child(program: &Program, name: String) -> Result<Program, ParseError> {
+    let program = program.borrow();
+    if let Some(child) = program.children.get(name.as_str()) {
+        return Ok(child.clone());
+    }
+    if let Some(child) = program.children.values().find(|child| child.borrow().aliases.contains(&name)) {
+        return Ok(child.clone());
+    }
+    let visible = program.visible_children();
+    let names: Vec<String> = visible
+        .iter()
+        .flat_map(|child| {
+            let child = child.borrow();
+            std::iter::once(child.name.clone()).chain(child.aliases.clone())
+        })
+        .collect();
+    Err(ParseError::UnknownCommand {
+        suggestion: error::suggest(&name, names.iter().map(String::as_str)),
+        name
+    })
+}
+
+#[derive(Clone)]
+pub struct ProgramArgument {
+    name: String,
+    description: String,
+    default: String,
+    values: Vec<String>,
+    parser: Rc<dyn ValueParser>,
+    validators: Vec<Validator>,
+    parsed: Vec<Rc<dyn Any>>,
+    /// Asked for without echo when prompted.
+    password: bool,
+    /// Asked for as a yes/no question when prompted.
+    confirm: bool
+}
+
+#[derive(Clone)]
+pub struct ProgramOption {
+    flags: String,
+    description: String,
+    default: String,
+    /// The value of an optional value that is left out, as set by `ProgramOption::preset`.
+    preset: Option<String>,
+    argument: ProgramArgument,
+    spec: FlagSpec,
+    env: Option<String>,
+    config_key: Option<String>,
+    source: ValueSource,
+    /// The value of every time the option was given, or its fallback.
+    values: Vec<String>,
+    repeat: Repeat,
+    delimiter: Option<char>,
+    conflicts: Vec<String>,
+    requires: Vec<String>,
+    group: Option<String>
+}
+
+/// What an option keeps when it is given more than once.
+#[derive(Clone, Copy, PartialEq)]
+enum Repeat {
+    /// The last value.
+    Last,
+    /// How many times it was given, as set by `ProgramOption::count`.
+    Count,
+    /// Every value, as set by `ProgramOption::append`.
+    Append
+}
+
+/// How many options of a group declared with `Program::group` must be given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupRule {
+    ExactlyOne,
+    AtLeastOne
+}
+
+impl fmt::Display for GroupRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupRule::ExactlyOne => write!(f, "exactly one"),
+            GroupRule::AtLeastOne => write!(f, "at least one")
+        }
+    }
+}
+
+impl ProgramArgument {
+    pub fn new(name: String, description: String) -> Self {
+        Self {
+            name,
+            description,
+            default: String::new(),
+            values: Vec::new(),
+            parser: Rc::new(StringParser),
+            validators: Vec::new(),
+            parsed: Vec::new(),
+            password: false,
+            confirm: false
+        }
+    }
+
+    pub fn default(mut self, default: String) -> ProgramArgument {
+        self.default = default;
+        self
+    }
+
+    /// Sets how the value is parsed. The default is parsed the same way.
+    pub fn parser(mut self, parser: impl ValueParser + 'static) -> ProgramArgument {
+        self.parser = Rc::new(parser);
+        self
+    }
+
+    /// Adds a check that the value must pass after it parsed.
+    pub fn validator(mut self, validator: Validator) -> ProgramArgument {
+        self.validators.push(validator);
+        self
+    }
+
+    /// Hides the answer when the argument is prompted for, as for a password.
+    pub fn password(mut self) -> ProgramArgument {
+        self.password = true;
+        self
+    }
+
+    /// Makes the argument a boolean that is prompted for as a yes/no question.
+    pub fn confirm(mut self) -> ProgramArgument {
+        self.confirm = true;
+        self.parser(BoolParser)
+    }
+
+    /// The name without its `<>`, `[]` or `...`, which is how the value is looked up in `Matches`.
+    pub fn key(&self) -> String {
+        self.name
+            .trim_start_matches(['<', '['])
+            .trim_end_matches(['>', ']'])
+            .trim_end_matches("...")
+            .to_string()
+    }
+
+    /// Parses the values, or the default if there are none, with this argument's parser and
+    /// validators. `kind` and `name` say what is being parsed in the error message.
+    fn resolve(&mut self, kind: &str, name: &str, messages: &Catalog) -> Result<(), ParseError> {
+        self.parsed.clear();
+        let raws = match (self.values.is_empty(), self.default.is_empty()) {
+            (false, _) => self.values.clone(),
+            (true, false) => vec![self.default.clone()],
+            (true, true) => return Ok(())
+        };
+        for raw in raws {
+            let invalid = |reason: String| ParseError::InvalidValue {
+                kind: String::from(kind),
+                name: String::from(name),
+                value: raw.clone(),
+                reason
+            };
+            let parsed = self.check(&raw, messages).map_err(invalid)?;
+            self.parsed.push(parsed);
+        }
+        Ok(())
+    }
+
+    /// Parses `raw` and runs the validators on it, or says why it is not a valid value.
+    fn check(&self, raw: &str, messages: &Catalog) -> Result<Rc<dyn Any>, String> {
+        let parsed = self.parser.parse(raw).ok_or_else(|| {
+            let expected = match self.parser.kind() {
+                Some("enum") => messages.format("value.enum", &[("choices", &self.parser.choices().unwrap_or_default().join(", "))]),
+                Some(kind) => String::from(messages.get(&format!("value.{}", kind))),
+                None => self.parser.expected()
+            };
+            messages.format("value.expected", &[("expected", &expected)])
+        })?;
+        for validator in &self.validators {
+            validator(raw)?;
+        }
+        Ok(parsed)
+    }
+
+    fn is_required(&self) -> bool {
+        self.name.starts_with('<')
+    }
+
+    /// Whether the argument collects all remaining positionals, as in `<files...>`.
+    fn is_variadic(&self) -> bool {
+        self.name.ends_with("...>") || self.name.ends_with("...]")
+    }
+
+    /// The first parsed value, or the default when nothing was given on the command line.
+    #[allow(dead_code)]
+    pub fn value(&self) -> &str {
+        self.values.first().unwrap_or(&self.default)
+    }
+
+    /// Every value given on the command line. Only a variadic argument can have more than one.
+    #[allow(dead_code)]
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    #[allow(dead_code)]
+    fn print(&self) {
+        println!("{}, {}, {}", self.name, self.description, self.default);
+    }
+}
+
+impl ProgramOption {
+    /// Creates an option from flags such as `"-o, --output <file>"`. A `<value>` or `[value]`
+    /// placeholder in the flags becomes the option's argument.
+    ///
+    /// Panics if the flags cannot be parsed, since that is a mistake in the program definition
+    /// rather than in the user's input.
+    pub fn new(flags: String, description: String) -> Self {
+        let spec = FlagSpec::parse(&flags).unwrap_or_else(|error| panic!("{}", error));
+        let argument = match &spec.placeholder {
+            Some(placeholder) if placeholder.required => format!("<{}>", placeholder.name),
+            Some(placeholder) => format!("[{}]", placeholder.name),
+            None => String::new()
+        };
+        let default = if spec.negate { String::from("true") } else { String::new() };
+        let mut argument = ProgramArgument::new(argument, description.clone());
+        if spec.placeholder.is_none() {
+            argument = argument.parser(BoolParser);
+        }
+        Self {
+            argument,
+            flags,
+            description,
+            default,
+            preset: None,
+            spec,
+            env: None,
+            config_key: None,
+            source: ValueSource::Default,
+            values: Vec::new(),
+            repeat: Repeat::Last,
+            delimiter: None,
+            conflicts: Vec::new(),
+            requires: Vec::new(),
+            group: None
+        }
+    }
+
+    pub fn default(mut self, default: String) -> ProgramOption {
+        self.default = default;
+        self
+    }
+
+    /// Sets the value an option with an optional value gets when it is given without one, as in
+    /// `--level` for `--level [n]`. Without a preset it gets its default, or `true` if it has
+    /// none. Panics if the option's value is not optional.
+    pub fn preset(mut self, preset: String) -> ProgramOption {
+        if !self.has_optional_value() {
+            panic!("Option '{}' cannot have a preset, its value is not optional", self.flags);
+        }
+        self.preset = Some(preset);
+        self
+    }
+
+    /// Sets how the option's value is parsed. Flags without a value are parsed as booleans.
+    pub fn parser(mut self, parser: impl ValueParser + 'static) -> ProgramOption {
+        self.argument = self.argument.parser(parser);
+        self
+    }
+
+    /// Adds a check that the option's value must pass after it parsed.
+    #[allow(dead_code)]
+    pub fn validator(mut self, validator: Validator) -> ProgramOption {
+        self.argument = self.argument.validator(validator);
+        self
+    }
+
+    /// Makes a flag count how many times it is given, so `-vvv` is the integer 3. It is 0 when
+    /// the flag is not given. Panics if the option takes a value or is a `--no-` flag.
+    pub fn count(mut self) -> ProgramOption {
+        if self.takes_value() || self.spec.negate {
+            panic!("Option '{}' cannot be counted, only a flag without a value can", self.flags);
+        }
+        self.repeat = Repeat::Count;
+        self.parser(IntegerParser)
+    }
+
+    /// Keeps every value the option is given instead of the last one, so `--include a --include b`
+    /// has the values `a` and `b`. Panics if the option does not take a value.
+    pub fn append(mut self) -> ProgramOption {
+        if !self.takes_value() {
+            panic!("Option '{}' cannot collect values, it does not take one", self.flags);
+        }
+        self.repeat = Repeat::Append;
+        self
+    }
+
+    /// Splits each value at `delimiter`, so `--tags a,b,c` has the values `a`, `b` and `c`. Values
+    /// from the environment, config file and default are split too. Panics if the option does not
+    /// take a value.
+    pub fn delimiter(mut self, delimiter: char) -> ProgramOption {
+        if !self.takes_value() {
+            panic!("Option '{}' cannot split its value, it does not take one", self.flags);
+        }
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Names an environment variable the option falls back to when it is not on the command line.
+    pub fn env(mut self, name: String) -> ProgramOption {
+        self.env = Some(name);
+        self
+    }
+
+    /// Names a `section.key` in the program's config file that the option falls back to when it
+    /// is neither on the command line nor in its environment variable.
+    pub fn config(mut self, key: String) -> ProgramOption {
+        self.config_key = Some(key);
+        self
+    }
+
+    /// Makes it an error to give both this option and the option `name`, e.g. `"verbose"`.
+    pub fn conflicts_with(mut self, name: String) -> ProgramOption {
+        self.conflicts.push(name);
+        self
+    }
+
+    /// Makes it an error to give this option without the option `name`.
+    pub fn requires(mut self, name: String) -> ProgramOption {
+        self.requires.push(name);
+        self
+    }
+
+    /// Adds the option to a group declared with `Program::group`.
+    pub fn group(mut self, name: String) -> ProgramOption {
+        self.group = Some(name);
+        self
+    }
+
+    /// Whether the option got its value from anywhere but its default.
+    fn is_given(&self) -> bool {
+        self.source != ValueSource::Default
+    }
+
+    /// How the option is shown in errors: its long flag, or its short flag if it has no long one.
+    fn flag(&self) -> String {
+        match (&self.spec.long, self.spec.short) {
+            (Some(long), _) if self.spec.negate => format!("--no-{}", long),
+            (Some(long), _) => format!("--{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => String::new()
+        }
+    }
+
+    /// The value from the option's environment variable or config key, whichever comes first.
+    fn fallback(&self, config: &Config, env: Option<&HashMap<String, String>>) -> Option<(String, ValueSource)> {
+        if let Some(name) = &self.env {
+            let value = match env {
+                Some(env) => env.get(name).cloned(),
+                None => std::env::var(name).ok()
+            };
+            if let Some(value) = value {
+                return Some((value, ValueSource::Env(name.clone())));
+            }
+        }
+        let key = self.config_key.as_ref()?;
+        config.get(key).map(|value| (String::from(value), ValueSource::Config(key.clone())))
+    }
+
+    /// The name the option is known by in `Matches`: its long flag, or its short flag if it has
+    /// no long one.
+    pub fn name(&self) -> String {
+        self.spec.name()
+    }
+
+    /// Parses the values the option keeps, given how it is repeated and split, or its default.
+    fn resolve(&mut self, messages: &Catalog) -> Result<(), ParseError> {
+        let values = match self.repeat {
+            Repeat::Count if self.source == ValueSource::CommandLine => vec![self.values.len().to_string()],
+            Repeat::Append => self.values.clone(),
+            _ => self.values.last().cloned().into_iter().collect()
+        };
+        let values = match (values.is_empty(), self.repeat) {
+            (false, _) => values,
+            (true, _) if !self.default.is_empty() => vec![self.default.clone()],
+            (true, Repeat::Count) => vec![String::from("0")],
+            (true, _) => Vec::new()
+        };
+        self.argument.values = match self.delimiter {
+            Some(delimiter) => values.iter().flat_map(|value| value.split(delimiter)).map(String::from).collect(),
+            None => values
+        };
+        self.argument.resolve("option", &self.name(), messages)
+    }
+
+    fn takes_value(&self) -> bool {
+        self.spec.placeholder.is_some()
+    }
+
+    /// Whether the option takes a value that can be left out, as in `--level [n]`.
+    fn has_optional_value(&self) -> bool {
+        self.spec.placeholder.as_ref().is_some_and(|placeholder| !placeholder.required)
+    }
+
+    /// Sets the value from `inline` (`--opt=value`, `-ovalue`) or the next argument. A required
+    /// value always takes the next argument; an optional one only when it is not another flag,
+    /// and is the preset, the default or `true` otherwise.
+    fn take_value(&mut self, flag: &str, inline: Option<String>, rest: &mut Peekable<Iter<String>>) -> Result<(), ParseError> {
+        let required = match &self.spec.placeholder {
+            Some(placeholder) => placeholder.required,
+            None if inline.is_some() => return Err(ParseError::UnexpectedValue { option: String::from(flag) }),
+            None => {
+                self.values.push(String::from("true"));
+                return Ok(());
+            }
+        };
+        let value = match inline {
+            Some(value) => value,
+            None => match rest.next_if(|next| self.takes_as_value(next)) {
+                Some(next) => next.clone(),
+                None if required => return Err(ParseError::MissingValue { option: String::from(flag) }),
+                None => match &self.preset {
+                    Some(preset) => preset.clone(),
+                    None if !self.default.is_empty() => self.default.clone(),
+                    None => String::from("true")
+                }
+            }
+        };
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Whether the option, given without an inline value, takes `next` as its value: always when
+    /// the value is required, and unless `next` looks like an option when it is optional.
+    fn takes_as_value(&self, next: &str) -> bool {
+        self.spec.placeholder.as_ref().is_some_and(|placeholder| placeholder.required || !next.starts_with('-'))
+    }
+
+    /// The last value given, or the default when the option was not given.
+    #[allow(dead_code)]
+    pub fn value(&self) -> &str {
+        self.values.last().unwrap_or(&self.default)
+    }
+
+    /// Every value given, in order, before they are split at the delimiter.
+    #[allow(dead_code)]
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+}
+
+pub fn run() {
+    println!("This is how you mimic a `class` in Rustlang!")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::testing::TempDir;
+    use super::value::FloatParser;
+    use std::cell::Ref;
+
+    #[test]
+    fn program_action_is_set() {
+        let mut program = Program::new(String::from("root"));
+        fn help(context: &mut Context) -> ActionResult {
+            println!("{:?}", context.arguments().len());
+            println!("{:?}", context.options().len());
+            Ok(())
+        }
+        program = program.action(help);
+        assert!(program.borrow().action.is_some());
+    }
+
+    #[test]
+    fn program_description_is_set() {
+        let mut program = Program::new(String::from("root"));
+        program = program.description(String::from("root command"));
+        match program.borrow().description.clone() {
+            Some(description) => assert_eq!(description, String::from("root command")),
+            None => panic!("Failed to get description")
+        };
+    }
+
+    #[test]
+    fn program_command_is_found() {
+        let parent = Program::new(String::from("root"));
+        let child = parent.command(String::from("command"));
+        let valid_command = get_child(&parent, String::from("command"));
+        match valid_command {
+            Ok(cmd) => assert_eq!(*Ref::map(cmd.borrow(), |n| &n.name), String::from("command")),
+            Err(_) => panic!("Failed to get the command")
+        }
+        let invalid_command = get_child(&parent, String::from("leaf"));
+        match invalid_command {
+            Ok(cmd) => panic!("Found command that should be invalid"),
+            Err(_) => return
+        }
+    }
+
+    #[test]
+    fn parse_prints_help_by_default() {
+        let stdout = Buffer::default();
+        let program = Program::new(String::from("root")).stdout(stdout.clone());
+        let command = program.command(String::from("command"));
+        assert!(program.parse_from(Vec::<String>::new()).is_ok());
+        assert_eq!(stdout.text(), format!("{}\n", program.help()));
+    }
+
+    #[test]
+    fn inequality_symbol_means_required() {
+        let arg = ProgramArgument::new(String::from("<my_required_arg>"), String::from("my required argument"))
+            .default(String::from("run"));
+        assert!(arg.is_required())
+    }
+
+    /// A writer whose contents can still be read after it is handed to a `Program` or `Prompter`.
+    #[derive(Clone, Default)]
+    pub(super) struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Buffer {
+        pub(super) fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    thread_local! {
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(context: &mut Context) -> ActionResult {
+        let mut call = Vec::new();
+        for argument in context.arguments() {
+            call.push(format!("{}={}", argument.name, argument.values().join(",")));
+        }
+        for option in context.options() {
+            call.push(format!("{}={}", option.flags, option.value()));
+        }
+        CALLS.with(|calls| calls.borrow_mut().push(call.join(" ")));
+        Ok(())
+    }
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| String::from(*value)).collect()
+    }
+
+    fn last_call() -> Option<String> {
+        CALLS.with(|calls| calls.borrow().last().cloned())
+    }
+
+    #[test]
+    fn help_flag_is_handled_on_any_command() {
+        let stdout = Buffer::default();
+        let leaf = Program::new(String::from("leaf"))
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("a name")));
+        let root = Program::new(String::from("root")).stdout(stdout.clone()).add_command(leaf);
+        assert!(root.dispatch(&args(&["leaf", "--help"])).is_ok());
+        let help = stdout.text();
+        assert!(help.starts_with("Usage: root leaf [options] <name>\n"));
+        assert!(help.contains("\nArguments:\n  name        a name\n"));
+        assert!(help.contains("\nOptions:\n  -h, --help  display help for command\n"));
+        assert!(root.dispatch(&args(&["-h"])).is_ok());
+        assert!(stdout.text()[help.len()..].starts_with("Usage: root [options] [command]\n"));
+        assert!(wants_help(&scan_flags(&args(&["a", "-vh"]), &[])));
+        assert!(!wants_help(&scan_flags(&args(&["--", "-h"]), &[])));
+    }
+
+    #[test]
+    fn help_flags_in_value_positions_are_values() {
+        let root = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-m, --message <text>"), String::from("message")))
+            .action(record);
+        root.dispatch(&args(&["-m", "-h"])).unwrap();
+        assert_eq!(last_call(), Some(String::from("-m, --message <text>=-h")));
+        root.dispatch(&args(&["--message", "--help"])).unwrap();
+        assert_eq!(last_call(), Some(String::from("-m, --message <text>=--help")));
+        let options = [ProgramOption::new(String::from("-m <text>"), String::new())];
+        let flags = scan_flags(&args(&["x", "-mh", "--no-x", "-h"]), &options.iter().collect::<Vec<_>>());
+        assert_eq!(flags, vec![
+            (String::from("-m"), Some(String::from("h"))),
+            (String::from("--no-x"), None),
+            (String::from("-h"), None)
+        ]);
+    }
+
+    #[test]
+    fn parse_dispatches_to_nested_command() {
+        let bench = Program::new(String::from("bench"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("list size")))
+            .option(ProgramOption::new(String::from("-r, --runs <count>"), String::from("runs")).default(String::from("1")))
+            .action(record);
+        let lists = Program::new(String::from("lists")).add_command(bench);
+        let root = Program::new(String::from("root")).add_command(lists);
+
+        root.dispatch(&args(&["lists", "bench", "10", "--runs", "3"])).unwrap();
+        assert_eq!(last_call().unwrap(), "<size>=10 -r, --runs <count>=3");
+
+        root.dispatch(&args(&["lists", "bench", "20"])).unwrap();
+        assert_eq!(last_call().unwrap(), "<size>=20 -r, --runs <count>=1");
+    }
+
+    #[test]
+    fn parse_reports_bad_arguments() {
+        let leaf = Program::new(String::from("leaf"))
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("a name")))
+            .action(record);
+        let root = Program::new(String::from("root")).add_command(leaf);
+
+        assert!(root.dispatch(&args(&[])).is_ok());
+        assert_eq!(root.dispatch(&args(&["lea"])).unwrap_err().to_string(), "Unknown command 'lea' (Did you mean 'leaf'?)");
+        assert_eq!(root.dispatch(&args(&["leaf"])).unwrap_err().to_string(), "Missing required argument '<name>'");
+        assert_eq!(root.dispatch(&args(&["leaf", "a", "b"])).unwrap_err().to_string(), "Unexpected argument 'b'");
+        assert_eq!(root.dispatch(&args(&["leaf", "--nope"])).unwrap_err().to_string(), "Unknown option '--nope'");
+    }
+
+    #[test]
+    fn parse_runs_root_action_without_command() {
+        let root = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-q, --quiet"), String::from("be quiet")))
+            .action(record);
+        root.dispatch(&args(&["-q"])).unwrap();
+        assert_eq!(last_call().unwrap(), "-q, --quiet=true");
+    }
+
+    fn flags_program() -> Program {
+        Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("[file]"), String::from("a file")))
+            .option(ProgramOption::new(String::from("-a, --all"), String::from("all")))
+            .option(ProgramOption::new(String::from("-b"), String::from("b")))
+            .option(ProgramOption::new(String::from("-o, --output <file>"), String::from("output")))
+            .option(ProgramOption::new(String::from("-l, --level [n]"), String::from("level")))
+            .option(ProgramOption::new(String::from("--no-color"), String::from("color")))
+            .action(record)
+    }
+
+    #[test]
+    fn parse_combines_short_flags() {
+        flags_program().dispatch(&args(&["-abo", "out.txt"])).unwrap();
+        assert_eq!(last_call().unwrap(), "[file]= -a, --all=true -b=true -o, --output <file>=out.txt -l, --level [n]= --no-color=true");
+
+        flags_program().dispatch(&args(&["-aoout.txt"])).unwrap();
+        assert!(last_call().unwrap().contains("--output <file>=out.txt"));
+    }
+
+    #[test]
+    fn parse_reads_long_option_values() {
+        flags_program().dispatch(&args(&["--output=a.txt", "--level", "3"])).unwrap();
+        let call = last_call().unwrap();
+        assert!(call.contains("--output <file>=a.txt"));
+        assert!(call.contains("--level [n]=3"));
+
+        flags_program().dispatch(&args(&["--level", "--all"])).unwrap();
+        let call = last_call().unwrap();
+        assert!(call.contains("--level [n]=true"));
+        assert!(call.contains("--all=true"));
+
+        assert_eq!(flags_program().dispatch(&args(&["--output"])).unwrap_err().to_string(), "Option '--output' expects a value");
+        assert_eq!(flags_program().dispatch(&args(&["--all=yes"])).unwrap_err().to_string(), "Option '--all' does not take a value");
+    }
+
+    #[test]
+    fn optional_values_left_out_are_the_preset_or_default() {
+        let level = || ProgramOption::new(String::from("-l, --level [n]"), String::from("level")).parser(IntegerParser);
+        let root = Program::new(String::from("root")).option(level().preset(String::from("3"))).action(record);
+        let matches = root.dispatch(&args(&["--level"])).unwrap();
+        assert_eq!(matches.get::<i64>("level"), Some(&3));
+        assert_eq!(root.dispatch(&args(&["-l", "5"])).unwrap().get::<i64>("level"), Some(&5));
+
+        let root = Program::new(String::from("root")).option(level().default(String::from("1"))).action(record);
+        assert_eq!(root.dispatch(&args(&["-l"])).unwrap().get::<i64>("level"), Some(&1));
+        let root = Program::new(String::from("root")).option(level()).action(record);
+        assert_eq!(
+            root.dispatch(&args(&["-l"])).unwrap_err().to_string(),
+            "Invalid value 'true' for option 'level': expected an integer"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Option '--level <n>' cannot have a preset, its value is not optional")]
+    fn presets_need_an_optional_value() {
+        ProgramOption::new(String::from("--level <n>"), String::from("level")).preset(String::from("1"));
+    }
+
+    #[test]
+    fn parse_negates_flags() {
+        flags_program().dispatch(&args(&["--no-color", "--no-all"])).unwrap();
+        let call = last_call().unwrap();
+        assert!(call.contains("--no-color=false"));
+        assert!(call.contains("--all=false"));
+
+        flags_program().dispatch(&args(&["--color"])).unwrap();
+        assert!(last_call().unwrap().contains("--no-color=true"));
+    }
+
+    #[test]
+    fn parse_stops_at_double_dash() {
+        flags_program().dispatch(&args(&["--", "-a"])).unwrap();
+        let call = last_call().unwrap();
+        assert!(call.contains("[file]=-a"));
+        assert!(call.contains("--all="));
+    }
+
+    #[test]
+    fn parse_returns_typed_matches() {
+        let program = Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .argument(ProgramArgument::new(String::from("[ratio]"), String::from("ratio")).parser(FloatParser).default(String::from("0.5")))
+            .option(ProgramOption::new(String::from("-s, --shell <name>"), String::from("shell"))
+                .parser(EnumParser(vec![String::from("bash"), String::from("zsh")])))
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbose")))
+            .option(ProgramOption::new(String::from("-q"), String::from("quiet")))
+            .action(record);
+
+        let matches = program.dispatch(&args(&["10", "--shell", "zsh", "-v"])).unwrap();
+        assert_eq!(matches.get::<i64>("size"), Some(&10));
+        assert_eq!(matches.get::<f64>("ratio"), Some(&0.5));
+        assert_eq!(matches.get::<String>("shell").map(String::as_str), Some("zsh"));
+        assert!(matches.flag("verbose"));
+        assert!(!matches.flag("q"));
+        assert_eq!(matches.get::<String>("size"), None);
+    }
+
+    #[test]
+    fn parse_names_the_invalid_value() {
+        fn below_ten(raw: &str) -> Result<(), String> {
+            match raw.parse::<i64>() {
+                Ok(value) if value < 10 => Ok(()),
+                _ => Err(String::from("must be below 10"))
+            }
+        }
+        let program = Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser).validator(below_ten))
+            .option(ProgramOption::new(String::from("--shell <name>"), String::from("shell"))
+                .parser(EnumParser(vec![String::from("bash"), String::from("zsh")])))
+            .action(record);
+
+        assert_eq!(program.dispatch(&args(&["ten"])).unwrap_err().to_string(), "Invalid value 'ten' for argument 'size': expected an integer");
+        assert_eq!(program.dispatch(&args(&["12"])).unwrap_err().to_string(), "Invalid value '12' for argument 'size': must be below 10");
+        assert_eq!(
+            program.dispatch(&args(&["1", "--shell", "fish"])).unwrap_err().to_string(),
+            "Invalid value 'fish' for option 'shell': expected one of bash, zsh"
+        );
+    }
+
+    #[test]
+    fn parse_suggests_close_matches() {
+        let program = Program::new(String::from("root"))
+            .add_command(Program::new(String::from("bench")).option(ProgramOption::new(String::from("--runs <count>"), String::from("runs"))))
+            .add_command(Program::new(String::from("list")));
+
+        assert_eq!(
+            program.dispatch(&args(&["bnech"])).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("bnech"), suggestion: Some(String::from("bench")) }
+        );
+        assert_eq!(
+            program.dispatch(&args(&["bench", "--rusn", "3"])).unwrap_err(),
+            ParseError::UnknownOption { name: String::from("--rusn"), suggestion: Some(String::from("--runs")) }
+        );
+        assert_eq!(program.dispatch(&args(&["bench", "--rusn"])).unwrap_err().exit_code(), 3);
+        assert_eq!(program.dispatch(&args(&["bench"])).unwrap_err().exit_code(), 10);
+    }
+
+    #[test]
+    fn parse_suggests_flags_for_unknown_short_flags() {
+        let program = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbose")))
+            .option(ProgramOption::new(String::from("-n, --dry-run"), String::from("dry run")))
+            .action(record);
+
+        let unknown = |name: &str, suggestion: Option<&str>| ParseError::UnknownOption {
+            name: String::from(name),
+            suggestion: suggestion.map(String::from)
+        };
+        assert_eq!(program.dispatch(&args(&["-verbsoe"])).unwrap_err(), unknown("-verbsoe", Some("--verbose")));
+        assert_eq!(program.dispatch(&args(&["-dry-run"])).unwrap_err(), unknown("-dry-run", Some("--dry-run")));
+        assert_eq!(program.dispatch(&args(&["-V"])).unwrap_err(), unknown("-V", Some("-v")));
+        assert_eq!(program.dispatch(&args(&["-vN"])).unwrap_err(), unknown("-N", Some("-n")));
+        assert_eq!(program.dispatch(&args(&["-x"])).unwrap_err(), unknown("-x", None));
+    }
+
+    #[test]
+    fn variadic_argument_collects_the_rest() {
+        let program = Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .argument(ProgramArgument::new(String::from("<files...>"), String::from("files")))
+            .action(record);
+
+        let matches = program.dispatch(&args(&["3", "a", "b", "c"])).unwrap();
+        assert_eq!(last_call().unwrap(), "<size>=3 <files...>=a,b,c");
+        assert_eq!(matches.get::<i64>("size"), Some(&3));
+        assert_eq!(matches.get_many::<String>("files"), vec!["a", "b", "c"]);
+        assert_eq!(program.dispatch(&args(&["3"])).unwrap_err(), ParseError::MissingArgument { name: String::from("<files...>") });
+    }
+
+    #[test]
+    fn optional_variadic_argument_can_be_empty() {
+        let program = Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("[first]"), String::from("first")))
+            .argument(ProgramArgument::new(String::from("[rest...]"), String::from("rest")).parser(IntegerParser))
+            .action(record);
+
+        let matches = program.dispatch(&args(&[])).unwrap();
+        assert!(!matches.contains("first"));
+        assert!(matches.get_many::<i64>("rest").is_empty());
+
+        let matches = program.dispatch(&args(&["x", "1", "2"])).unwrap();
+        assert_eq!(matches.get_many::<i64>("rest"), vec![&1, &2]);
+        assert_eq!(
+            program.dispatch(&args(&["x", "1", "two"])).unwrap_err().to_string(),
+            "Invalid value 'two' for argument 'rest': expected an integer"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot follow the variadic argument")]
+    fn variadic_argument_must_come_last() {
+        Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("<files...>"), String::from("files")))
+            .argument(ProgramArgument::new(String::from("[other]"), String::from("other")));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot follow the optional argument")]
+    fn required_argument_cannot_follow_optional() {
+        Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("[first]"), String::from("first")))
+            .argument(ProgramArgument::new(String::from("<second>"), String::from("second")));
+    }
+
+    #[test]
+    fn options_fall_back_to_env_then_config_then_default() {
+        let dir = TempDir::new("config");
+        let file = dir.join("config.toml");
+        std::fs::write(&file, "[bench]\nruns = 5\nsize = 7\n").unwrap();
+        let env = HashMap::from([(String::from("RUNS"), String::from("9"))]);
+        let inherited = Inherited { env: Some(env), ..Inherited::default() };
+
+        let option = |flags: &str, key: &str, env: &str| {
+            ProgramOption::new(String::from(flags), String::new())
+                .parser(IntegerParser)
+                .default(String::from("1"))
+                .config(String::from(key))
+                .env(String::from(env))
+        };
+        let program = Program::new(String::from("root"))
+            .config_file(file)
+            .add_command(
+                Program::new(String::from("bench"))
+                    .option(option("--runs <n>", "bench.runs", "RUNS"))
+                    .option(option("--size <n>", "bench.size", "SIZE"))
+                    .option(option("--warmup <n>", "bench.warmup", "WARMUP"))
+                    .action(record)
+            );
+
+        let matches = program.dispatch_from(&inherited, &args(&["bench"])).unwrap();
+        assert_eq!(matches.get::<i64>("runs"), Some(&9));
+        assert_eq!(matches.source("runs"), Some(&ValueSource::Env(String::from("RUNS"))));
+        assert_eq!(matches.get::<i64>("size"), Some(&7));
+        assert_eq!(matches.source("size"), Some(&ValueSource::Config(String::from("bench.size"))));
+        assert_eq!(matches.get::<i64>("warmup"), Some(&1));
+        assert_eq!(matches.source("warmup"), Some(&ValueSource::Default));
+
+        let matches = program.dispatch_from(&inherited, &args(&["bench", "--runs", "2"])).unwrap();
+        assert_eq!(matches.get::<i64>("runs"), Some(&2));
+        assert_eq!(matches.source("runs"), Some(&ValueSource::CommandLine));
+
+        std::fs::write(dir.join("config.toml"), "runs\n").unwrap();
+        assert_eq!(program.dispatch_from(&inherited, &args(&["bench"])).unwrap_err().exit_code(), 11);
+    }
+
+    #[test]
+    fn changes_through_any_handle_reach_the_root() {
+        let root = Program::new(String::from("root"));
+        let bench = root
+            .command(String::from("lists"))
+            .description(String::from("linked lists"))
+            .command(String::from("bench"));
+        bench.clone().argument(ProgramArgument::new(String::from("<size>"), String::from("size"))).action(record);
+
+        let found = get_child(&get_child(&root, String::from("lists")).unwrap(), String::from("bench")).unwrap();
+        assert!(Rc::ptr_eq(&found.0, &bench.0));
+        assert_eq!(bench.path(), vec!["root", "lists", "bench"]);
+        assert_eq!(bench.parent().unwrap().borrow().description.as_deref(), Some("linked lists"));
+        assert!(is_root(&root));
+
+        root.dispatch(&args(&["lists", "bench", "3"])).unwrap();
+        assert_eq!(last_call().unwrap(), "<size>=3");
+    }
+
+    #[test]
+    fn children_do_not_keep_the_tree_alive() {
+        let root = Program::new(String::from("root"));
+        let child = root.command(String::from("child"));
+        drop(root);
+        assert!(child.parent().is_none());
+    }
+
+    #[test]
+    fn adding_a_command_again_moves_it() {
+        let (first, second) = (Program::new(String::from("first")), Program::new(String::from("second")));
+        let child = first.command(String::from("child"));
+        let second = second.add_command(child.clone());
+        assert!(first.borrow().children.is_empty());
+        assert_eq!(child.path(), vec!["second", "child"]);
+        assert!(get_child(&second, String::from("child")).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Command 'root' cannot be added below itself")]
+    fn commands_cannot_be_added_below_themselves() {
+        let root = Program::new(String::from("root"));
+        let leaf = root.command(String::from("lists")).command(String::from("bench"));
+        leaf.add_command(root);
+    }
+
+    #[test]
+    fn version_flag_needs_a_version() {
+        let root = Program::new(String::from("root")).version(String::from("1.2.3")).action(record);
+        let leaf = root.command(String::from("leaf")).action(record);
+        assert!(root.dispatch(&args(&["--version"])).is_ok());
+        assert!(root.dispatch(&args(&["-V"])).is_ok());
+        assert_eq!(leaf.dispatch(&args(&["-V"])).unwrap_err(), ParseError::UnknownOption { name: String::from("-V"), suggestion: None });
+    }
+
+    #[test]
+    fn version_flags_in_value_positions_are_values() {
+        let stdout = Buffer::default();
+        let root = Program::new(String::from("root"))
+            .version(String::from("1.2.3"))
+            .stdout(stdout.clone())
+            .option(ProgramOption::new(String::from("-m <text>"), String::from("message")))
+            .action(record);
+        root.dispatch(&args(&["-m", "--version"])).unwrap();
+        assert_eq!(last_call(), Some(String::from("-m <text>=--version")));
+        root.dispatch(&args(&["-m-V", "-V"])).unwrap();
+        assert_eq!(stdout.text(), "1.2.3\n");
+    }
+
+    #[test]
+    fn help_command_follows_nested_paths() {
+        let stdout = Buffer::default();
+        let root = Program::new(String::from("root")).stdout(stdout.clone());
+        root.command(String::from("lists"))
+            .command(String::from("bench"))
+            .description(String::from("Times the lists."))
+            .option(ProgramOption::new(String::from("-r, --runs <count>"), String::from("how often to run")));
+
+        assert!(root.dispatch(&args(&["help"])).is_ok());
+        assert_eq!(stdout.text(), format!("{}\n", root.help()));
+        let before = stdout.text().len();
+        assert!(root.dispatch(&args(&["help", "lists", "bench"])).is_ok());
+        let help = &stdout.text()[before..];
+        assert!(help.starts_with("Usage: root lists bench [options]\n\nTimes the lists.\n"));
+        assert!(help.contains("  -r, --runs <count>  how often to run\n"));
+        assert_eq!(
+            root.dispatch(&args(&["help", "lists", "bnech"])).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("bnech"), suggestion: Some(String::from("bench")) }
+        );
+    }
+
+    #[test]
+    fn aliases_and_hidden_commands_dispatch() {
+        let root = Program::new(String::from("root"));
+        root.command(String::from("list")).alias(String::from("ls")).action(record);
+        root.command(String::from("secret")).hidden().action(record);
+
+        assert!(root.dispatch(&args(&["ls"])).is_ok());
+        assert!(root.dispatch(&args(&["secret"])).is_ok());
+        assert_eq!(
+            root.dispatch(&args(&["lss"])).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("lss"), suggestion: Some(String::from("ls")) }
+        );
+        assert_eq!(
+            root.dispatch(&args(&["secrte"])).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("secrte"), suggestion: None }
+        );
+    }
+
+    /// A `Logger` whose lines can still be read after it is handed to a program.
+    #[derive(Clone, Default)]
+    struct SharedLogger(Rc<RefCell<Vec<String>>>);
+
+    impl Logger for SharedLogger {
+        fn print(&mut self, value: &std::fmt::Arguments<'_>) {
+            self.0.borrow_mut().push(value.to_string());
+        }
+    }
+
+    #[test]
+    fn actions_capture_state_and_print_to_the_output() {
+        let logger = SharedLogger::default();
+        let runs = Rc::new(RefCell::new(0));
+        let counter = runs.clone();
+        let root = Program::new(String::from("root")).output(logger.clone());
+        root.command(String::from("lists"))
+            .command(String::from("bench"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .action(move |context| {
+                *counter.borrow_mut() += 1;
+                let size = context.matches().get::<i64>("size").unwrap();
+                context.print(&format_args!("{} {}", context.path().join(" "), size));
+                Ok(())
+            });
+
+        root.dispatch(&args(&["lists", "bench", "3"])).unwrap();
+        root.dispatch(&args(&["lists", "bench", "4"])).unwrap();
+        assert_eq!(*runs.borrow(), 2);
+        assert_eq!(*logger.0.borrow(), vec!["root lists bench 3", "root lists bench 4"]);
+    }
+
+    #[test]
+    fn actions_see_options_of_their_ancestors() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = seen.clone();
+        let root = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbose")))
+            .option(ProgramOption::new(String::from("-c, --color <when>"), String::from("color")).default(String::from("auto")));
+        root.command(String::from("lists"))
+            .argument(ProgramArgument::new(String::from("[name]"), String::from("name")))
+            .action(move |context| {
+                let globals = context.globals();
+                sink.borrow_mut().push(format!(
+                    "{} {} {:?}",
+                    globals.flag("verbose"),
+                    globals.get::<String>("color").unwrap(),
+                    context.matches().get::<String>("name")
+                ));
+                Ok(())
+            });
+
+        root.dispatch(&args(&["-v", "--color", "never", "lists", "a"])).unwrap();
+        root.dispatch(&args(&["-c", "lists", "lists", "-v"])).unwrap();
+        root.dispatch(&args(&["lists"])).unwrap();
+        assert_eq!(*seen.borrow(), vec![
+            "true never Some(\"a\")",
+            "true lists None",
+            "false auto None"
+        ]);
+        assert_eq!(
+            root.dispatch(&args(&["-v", "lsts"])).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("lsts"), suggestion: Some(String::from("lists")) }
+        );
+    }
+
+    #[test]
+    fn action_errors_become_the_exit_code() {
+        let root = Program::new(String::from("root"))
+            .action(|_| Err(ActionError::new(String::from("no lists found")).code(42)));
+        let error = root.dispatch(&args(&[])).unwrap_err();
+        assert_eq!(error.to_string(), "Command 'root' failed: no lists found");
+        assert_eq!(error.exit_code(), 42);
+    }
+
+    #[test]
+    fn counted_flags_count_each_time_they_are_given() {
+        let root = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbosity")).count())
+            .option(ProgramOption::new(String::from("-q, --quiet"), String::from("quiet")))
+            .action(record);
+
+        let matches = root.dispatch(&args(&["-vvqv", "--verbose"])).unwrap();
+        assert_eq!(matches.get::<i64>("verbose"), Some(&4));
+        assert!(matches.flag("quiet"));
+        assert_eq!(root.dispatch(&args(&[])).unwrap().get::<i64>("verbose"), Some(&0));
+        assert!(root.help().contains("verbosity (repeatable)"));
+        assert!(root.dispatch(&args(&["--no-quiet"])).is_ok());
+        assert!(matches!(
+            root.dispatch(&args(&["-v", "--no-verbose"])),
+            Err(ParseError::UnknownOption { name, .. }) if name == "--no-verbose"
+        ));
+    }
+
+    #[test]
+    fn appended_options_keep_every_value() {
+        let root = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-I, --include <dir>"), String::from("include")).append())
+            .option(ProgramOption::new(String::from("--tags <tags>"), String::from("tags")).delimiter(',').default(String::from("x,y")))
+            .option(
+                ProgramOption::new(String::from("-s, --size <sizes>"), String::from("sizes"))
+                    .append()
+                    .delimiter(',')
+                    .parser(IntegerParser)
+            )
+            .option(ProgramOption::new(String::from("--output <file>"), String::from("output")))
+            .action(record);
+
+        let matches = root
+            .dispatch(&args(&["-I", "a", "--include=b", "--tags", "c,d", "-s1,2", "--size", "3", "--output", "e", "--output", "f"]))
+            .unwrap();
+        assert_eq!(matches.get_many::<String>("include"), vec!["a", "b"]);
+        assert_eq!(matches.get_many::<String>("tags"), vec!["c", "d"]);
+        assert_eq!(matches.get_many::<i64>("size"), vec![&1, &2, &3]);
+        assert_eq!(matches.get_many::<String>("output"), vec!["f"]);
+
+        let matches = root.dispatch(&args(&[])).unwrap();
+        assert_eq!(matches.get_many::<String>("include").len(), 0);
+        assert_eq!(matches.get_many::<String>("tags"), vec!["x", "y"]);
+        assert_eq!(
+            root.dispatch(&args(&["-s", "1,two"])).unwrap_err(),
+            ParseError::InvalidValue {
+                kind: String::from("option"),
+                name: String::from("size"),
+                value: String::from("two"),
+                reason: String::from("expected an integer")
+            }
+        );
+        assert!(root.help().contains("sizes (repeatable) (separated by ',')"));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be counted")]
+    fn only_flags_can_be_counted() {
+        ProgramOption::new(String::from("--level <n>"), String::from("level")).count();
+    }
+
+    #[test]
+    fn hooks_run_around_the_actions_below_them() {
+        let logger = SharedLogger::default();
+        let log = |text: &'static str| move |context: &mut Context| {
+            context.print(&format_args!("{}", text));
+            Ok(())
+        };
+        let root = Program::new(String::from("root"))
+            .output(logger.clone())
+            .pre_action(log("root pre"))
+            .post_action(log("root post"));
+        root.command(String::from("lists"))
+            .pre_action(log("lists pre"))
+            .post_action(log("lists post"))
+            .command(String::from("bench"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .pre_action(|context| {
+                let size = *context.matches().get::<i64>("size").unwrap();
+                context.matches_mut().insert("size", size * 2);
+                Ok(())
+            })
+            .action(|context| {
+                context.print(&format_args!("bench {}", context.matches().get::<i64>("size").unwrap()));
+                Ok(())
+            });
+        root.command(String::from("fail")).action(|_| Err(ActionError::from("failed")));
+
+        let matches = root.dispatch(&args(&["lists", "bench", "3"])).unwrap();
+        assert_eq!(matches.get::<i64>("size"), Some(&6));
+        assert_eq!(*logger.0.borrow(), vec!["root pre", "lists pre", "bench 6", "lists post", "root post"]);
+
+        logger.0.borrow_mut().clear();
+        assert!(root.dispatch(&args(&["fail"])).is_err());
+        assert_eq!(*logger.0.borrow(), vec!["root pre"]);
+    }
+
+    #[test]
+    fn hooks_can_stop_the_action() {
+        let root = Program::new(String::from("root")).pre_action(|context| {
+            if context.globals().flag("dry-run") {
+                return Err(ActionError::new(String::from("dry run")).code(3));
+            }
+            Ok(())
+        });
+        root.clone().option(ProgramOption::new(String::from("-n, --dry-run"), String::from("dry run")));
+        root.command(String::from("lists")).action(|_| panic!("the hook should stop the action"));
+
+        let error = root.dispatch(&args(&["-n", "lists"])).unwrap_err();
+        assert_eq!(error.to_string(), "Command 'lists' failed: dry run");
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn unknown_commands_run_external_subcommands() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("external");
+        let script = dir.join("how_to_rust_external_test-greet");
+        let text = "#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/args\"\necho out\necho err >&2\nfor code; do :; done\nexit \"$code\"\n";
+        std::fs::write(&script, text).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let (stdout, stderr) = (Buffer::default(), Buffer::default());
+        let inherited = Inherited {
+            search_path: Some(OsString::from(dir.path())),
+            stdout: Some(Rc::new(RefCell::new(stdout.clone()))),
+            stderr: Some(Rc::new(RefCell::new(stderr.clone()))),
+            ..Inherited::default()
+        };
+        let dispatch = |program: &Program, values: &[&str]| program.dispatch_from(&inherited, &args(values));
+
+        let root = Program::new(String::from("how_to_rust_external_test")).external_subcommands();
+        root.command(String::from("list")).action(record);
+
+        assert!(dispatch(&root, &["greet", "--loud", "0"]).is_ok());
+        assert_eq!(std::fs::read_to_string(dir.join("args")).unwrap(), "--loud 0\n");
+        assert_eq!((stdout.text(), stderr.text()), (String::from("out\n"), String::from("err\n")));
+        assert!(dispatch(&root, &["--loud", "greet", "0"]).is_ok());
+        assert_eq!(std::fs::read_to_string(dir.join("args")).unwrap(), "--loud 0\n");
+        assert_eq!(
+            dispatch(&root, &["greet", "3"]).unwrap_err(),
+            ParseError::ExternalFailed { command: String::from("how_to_rust_external_test-greet"), code: 3 }
+        );
+        assert_eq!(dispatch(&root, &["greet", "3"]).unwrap_err().exit_code(), 3);
+        assert_eq!(
+            dispatch(&root, &["lsit"]).unwrap_err(),
+            ParseError::UnknownCommand { name: String::from("lsit"), suggestion: Some(String::from("list")) }
+        );
+
+        let closed = Program::new(String::from("how_to_rust_external_test"));
+        closed.command(String::from("list"));
+        assert!(matches!(dispatch(&closed, &["greet"]), Err(ParseError::UnknownCommand { .. })));
+    }
+
+    fn format_program() -> Program {
+        Program::new(String::from("root"))
+            .group(String::from("format"), GroupRule::ExactlyOne)
+            .group(String::from("source"), GroupRule::AtLeastOne)
+            .option(ProgramOption::new(String::from("--json"), String::from("json")).group(String::from("format")))
+            .option(ProgramOption::new(String::from("--yaml"), String::from("yaml")).group(String::from("format")))
+            .option(ProgramOption::new(String::from("--file <path>"), String::from("file")).group(String::from("source")))
+            .option(ProgramOption::new(String::from("--stdin"), String::from("stdin")).group(String::from("source")))
+            .option(ProgramOption::new(String::from("-q, --quiet"), String::from("quiet")).conflicts_with(String::from("verbose")))
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbose")))
+            .option(ProgramOption::new(String::from("--indent <n>"), String::from("indent")).requires(String::from("json")))
+            .action(record)
+    }
+
+    #[test]
+    fn options_follow_their_relations() {
+        let program = format_program();
+        assert!(program.dispatch(&args(&["--json", "--file", "a", "--stdin", "--indent", "2", "-v"])).is_ok());
+        assert_eq!(
+            program.dispatch(&args(&["--json", "--stdin", "-q", "-v"])).unwrap_err(),
+            ParseError::ConflictingOptions { option: String::from("--quiet"), other: String::from("--verbose") }
+        );
+        assert_eq!(
+            program.dispatch(&args(&["--yaml", "--stdin", "--indent", "2"])).unwrap_err(),
+            ParseError::MissingRequiredOption { option: String::from("--indent"), required: String::from("--json") }
+        );
+        assert_eq!(program.dispatch(&args(&["--yaml", "--stdin", "--indent", "2"])).unwrap_err().exit_code(), 13);
+    }
+
+    #[test]
+    fn groups_need_the_right_number_of_options() {
+        let program = format_program();
+        assert_eq!(
+            program.dispatch(&args(&["--stdin"])).unwrap_err().to_string(),
+            "One of '--json', '--yaml' is required (group 'format')"
+        );
+        assert_eq!(
+            program.dispatch(&args(&["--json", "--yaml", "--stdin"])).unwrap_err(),
+            ParseError::ConflictingOptions { option: String::from("--json"), other: String::from("--yaml") }
+        );
+        assert_eq!(
+            program.dispatch(&args(&["--json"])).unwrap_err(),
+            ParseError::MissingGroup { group: String::from("source"), options: vec![String::from("--file"), String::from("--stdin")] }
+        );
+    }
+
+    #[test]
+    fn option_groups_must_be_declared() {
+        let root = Program::new(String::from("root")).action(record);
+        root.command(String::from("lists"))
+            .option(ProgramOption::new(String::from("--json"), String::from("json")).group(String::from("format")))
+            .action(record);
+        let error = ParseError::UndeclaredGroup { option: String::from("--json"), group: String::from("format") };
+        assert_eq!(root.dispatch(&args(&[])).unwrap_err(), error);
+        assert_eq!(root.parse_from(["lists"]).unwrap_err(), error);
+        assert_eq!(error.exit_code(), 17);
+
+        let root = root.group(String::from("format"), GroupRule::AtLeastOne);
+        assert!(root.dispatch(&args(&["lists", "--json"])).is_ok());
+    }
+
+    #[test]
+    fn groups_apply_only_where_they_have_members() {
+        let export = Program::new(String::from("export"))
+            .option(ProgramOption::new(String::from("--json"), String::from("json")).group(String::from("format")))
+            .option(ProgramOption::new(String::from("--yaml"), String::from("yaml")).group(String::from("format")))
+            .action(record);
+        let root = Program::new(String::from("root")).group(String::from("format"), GroupRule::ExactlyOne).add_command(export);
+        root.command(String::from("list")).action(record);
+
+        assert!(root.dispatch(&args(&["list"])).is_ok());
+        assert!(root.dispatch(&args(&["export", "--yaml"])).is_ok());
+        assert_eq!(
+            root.dispatch(&args(&["export"])).unwrap_err(),
+            ParseError::MissingGroup { group: String::from("format"), options: vec![String::from("--json"), String::from("--yaml")] }
+        );
+    }
+
+    #[test]
+    fn missing_arguments_are_prompted_for() {
+        let root = Program::new(String::from("root"))
+            .prompter(Prompter::new("1 two\n1 2\n".as_bytes(), std::io::sink()))
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("name")))
+            .argument(ProgramArgument::new(String::from("<sizes...>"), String::from("sizes")).parser(IntegerParser))
+            .action(record);
+
+        let matches = root.dispatch(&args(&["lists"])).unwrap();
+        assert_eq!(matches.get::<String>("name").map(String::as_str), Some("lists"));
+        assert_eq!(matches.get_many::<i64>("sizes"), vec![&1, &2]);
+        assert_eq!(last_call().unwrap(), "<name>=lists <sizes...>=1,2");
+    }
+
+    #[test]
+    fn prompts_can_be_turned_off() {
+        let root = Program::new(String::from("root")).prompter(Prompter::new("lists\n".as_bytes(), std::io::sink()));
+        root.command(String::from("leaf"))
+            .no_prompts()
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("name")))
+            .action(record);
+        assert_eq!(root.dispatch(&args(&["leaf"])).unwrap_err(), ParseError::MissingArgument { name: String::from("<name>") });
+    }
+
+    #[test]
+    fn response_files_are_expanded_when_enabled() {
+        let dir = TempDir::new("args");
+        let file = dir.join("args.txt");
+        std::fs::write(&file, "lists\n'a b'").unwrap();
+        let at = format!("@{}", file.display());
+        let program = || {
+            Program::new(String::from("root"))
+                .argument(ProgramArgument::new(String::from("[words...]"), String::from("words")))
+                .action(record)
+        };
+
+        let matches = program().response_files().dispatch(&args(&[&at])).unwrap();
+        assert_eq!(matches.get_many::<String>("words"), vec!["lists", "a b"]);
+        let matches = program().dispatch(&args(&[&at])).unwrap();
+        assert_eq!(matches.get_many::<String>("words"), vec![&at]);
+        std::fs::remove_file(&file).unwrap();
+
+        let error = program().response_files().dispatch(&args(&[&at])).unwrap_err();
+        assert_eq!(error.exit_code(), 15);
+    }
+
+    #[test]
+    fn parse_from_writes_to_the_given_streams() {
+        let (stdout, stderr) = (Buffer::default(), Buffer::default());
+        let root = Program::new(String::from("root"))
+            .version(String::from("1.2.3"))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone());
+        root.command(String::from("greet"))
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("name")))
+            .action(|context: &mut Context| {
+                context.print(&format_args!("hello {}", context.matches().get::<String>("name").unwrap()));
+                Ok(())
+            });
+
+        assert_eq!(root.run_from(["greet", "ada"]), 0);
+        assert_eq!(root.run_from(["--version"]), 0);
+        assert_eq!(stdout.text(), "hello ada\n1.2.3\n");
+        assert_eq!(root.run_from(["gret"]), 2);
+        assert_eq!(stderr.text(), "error: Unknown command 'gret' (Did you mean 'greet'?)\n");
+        assert_eq!(stdout.text(), "hello ada\n1.2.3\n");
+    }
+
+    #[test]
+    fn parse_from_does_not_depend_on_the_terminal() {
+        let stdout = Buffer::default();
+        let root = Program::new(String::from("root")).stdout(stdout.clone());
+        root.command(String::from("greet"))
+            .description("a long description ".repeat(4).trim().to_string())
+            .argument(ProgramArgument::new(String::from("<name>"), String::from("name")))
+            .action(record);
+
+        assert_eq!(root.parse_from(["greet"]).unwrap_err(), ParseError::MissingArgument { name: String::from("<name>") });
+        root.parse_from(["--help"]).unwrap();
+        assert_eq!(stdout.text(), help::render(&root, &Config::default(), help::WIDTH, Style::default()) + "\n");
+    }
+
+    #[test]
+    fn messages_come_from_the_catalog() {
+        let stderr = Buffer::default();
+        let french = Catalog::new("fr")
+            .message("help.usage", "Utilisation : {usage}")
+            .message("help.commands", "Commandes")
+            .message("help.help", "afficher l'aide de la commande")
+            .message("error.prefix", "erreur : {message}")
+            .message("error.unknown_command", "Commande inconnue « {name} »")
+            .message("error.suggestion", " (Vouliez-vous dire « {suggestion} » ?)")
+            .message("error.invalid_value", "Valeur « {value} » invalide pour {kind} « {name} » : {reason}")
+            .message("kind.argument", "l'argument")
+            .message("value.expected", "{expected} attendu")
+            .message("value.integer", "un entier");
+        let root = Program::new(String::from("root")).messages(french).stderr(stderr.clone());
+        root.command(String::from("resize"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .action(record);
+
+        let help = root.help();
+        assert!(help.starts_with("Utilisation : root [options] [command]\n"));
+        assert!(help.contains("\nOptions:\n  -h, --help      afficher l'aide de la commande\n"));
+        assert!(help.contains("\nCommandes:\n"));
+        assert_eq!(root.run_from(["resiz"]), 2);
+        assert_eq!(root.run_from(["resize", "big"]), 6);
+        assert_eq!(
+            stderr.text(),
+            "erreur : Commande inconnue « resiz » (Vouliez-vous dire « resize » ?)\n\
+             erreur : Valeur « big » invalide pour l'argument « size » : un entier attendu\n"
+        );
+    }
+
+    #[test]
+    fn help_and_errors_are_styled_as_chosen() {
+        let (stdout, stderr) = (Buffer::default(), Buffer::default());
+        let root = Program::new(String::from("root")).color_flag().stdout(stdout.clone()).stderr(stderr.clone());
+        root.command(String::from("push")).action(record);
+
+        assert_eq!(root.run_from(["psh"]), 2);
+        assert_eq!(stderr.text(), "error: Unknown command 'psh' (Did you mean 'push'?)\n");
+        assert_eq!(root.run_from(["psh", "--color", "always"]), 2);
+        assert!(stderr.text().ends_with("\x1b[1;31merror:\x1b[0m Unknown command 'psh' (Did you mean '\x1b[1;33mpush\x1b[0m'?)\n"));
+
+        assert_eq!(root.run_from(["--color=always", "--help"]), 0);
+        assert!(stdout.text().contains("\x1b[1mCommands:\x1b[0m\n  \x1b[36mpush\x1b[0m"));
+        assert!(stdout.text().contains("  --color <when>  when to color help and errors (choices: auto, always, never)\n"));
+        assert_eq!(root.run_from(["push", "--color=sometimes"]), 6);
+        assert!(stderr.text().ends_with(
+            "error: Invalid value 'sometimes' for option 'color': expected one of auto, always, never\n"
+        ));
+    }
+
+    #[test]
+    fn the_color_flag_overrides_the_program_choice() {
+        let stdout = Buffer::default();
+        let root = Program::new(String::from("root")).color(ColorChoice::Always).stdout(stdout.clone());
+        assert_eq!(root.run_from(["--help"]), 0);
+        assert!(stdout.text().contains("\x1b[1mOptions:\x1b[0m"));
+        assert!(!root.help().contains('\x1b'));
+
+        let stdout = Buffer::default();
+        let root = root.color_flag().stdout(stdout.clone());
+        assert_eq!(root.run_from(["--help", "--color", "never"]), 0);
+        assert!(!stdout.text().contains('\x1b'));
+        assert_eq!(root.run_from(["--", "--color"]), 5);
+    }
+
+    #[test]
+    fn the_color_flag_is_read_only_in_option_positions() {
+        let stderr = Buffer::default();
+        let root = Program::new(String::from("root")).color_flag().stderr(stderr.clone());
+        root.command(String::from("push"))
+            .option(ProgramOption::new(String::from("-m, --message <text>"), String::from("message")))
+            .argument(ProgramArgument::new(String::from("[remote]"), String::from("remote")))
+            .action(record);
+
+        let matches = root.parse_from(["push", "-m", "--color", "always"]).unwrap();
+        assert_eq!(matches.get::<String>("message").map(String::as_str), Some("--color"));
+        assert_eq!(matches.get::<String>("remote").map(String::as_str), Some("always"));
+        assert_eq!(root.run_from(["push", "-m", "--color", "always", "extra"]), 5);
+        assert!(stderr.text().starts_with("error: "));
+        assert_eq!(root.run_from(["push", "--color", "always", "-m"]), 7);
+        assert!(stderr.text().ends_with("\x1b[1;31merror:\x1b[0m Option '-m' expects a value\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Option '--no-color' cannot be added, the program's color_flag takes --color")]
+    fn the_color_flag_rejects_options_added_below_it() {
+        let root = Program::new(String::from("root")).color_flag();
+        root.command(String::from("lists")).option(ProgramOption::new(String::from("--no-color"), String::from("plain")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Option '--color <when>' cannot be added, the program's color_flag takes --color")]
+    fn the_color_flag_rejects_options_already_below_it() {
+        let lists = Program::new(String::from("lists")).option(ProgramOption::new(String::from("--color <when>"), String::from("color")));
+        let root = Program::new(String::from("root")).add_command(lists);
+        root.color_flag();
+    }
+
+    #[test]
+    #[should_panic(expected = "Option '--no-color' cannot be added, the program's color_flag takes --color")]
+    fn the_color_flag_rejects_commands_with_a_color_option() {
+        let lists = Program::new(String::from("lists")).option(ProgramOption::new(String::from("--no-color"), String::from("plain")));
+        Program::new(String::from("root")).color_flag().add_command(lists);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_from_rejects_arguments_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+        let root = Program::new(String::from("root")).action(record);
+        assert_eq!(
+            root.parse_from([OsString::from_vec(vec![b'a', 0xff])]).unwrap_err(),
+            ParseError::InvalidEncoding { value: String::from("a\u{fffd}") }
+        );
+    }
+}
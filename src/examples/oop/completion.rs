@@ -0,0 +1,400 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use super::style::ColorChoice;
+use super::Program;
+
+/// A shell that `Program::completions` can write a completion script for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish
+}
+
+impl Shell {
+    pub const NAMES: [&'static str; 3] = ["bash", "zsh", "fish"];
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Shell, String> {
+        match name {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("Unknown shell '{}', expected one of {}", name, Shell::NAMES.join(", ")))
+        }
+    }
+}
+
+/// What can be completed at one command in the tree.
+struct Node {
+    /// The command names from the root down to this command, joined with spaces.
+    path: String,
+    commands: Vec<(String, String)>,
+    /// Other names of the commands, as `(alias, name)`. They are followed when walking the words
+    /// typed so far but are not offered themselves.
+    aliases: Vec<(String, String)>,
+    options: Vec<Flag>,
+    /// Values a positional argument of this command accepts.
+    values: Vec<String>
+}
+
+struct Flag {
+    short: Option<char>,
+    long: Option<String>,
+    description: String,
+    takes_value: bool,
+    choices: Vec<String>
+}
+
+impl Flag {
+    fn words(&self) -> Vec<String> {
+        let short = self.short.map(|short| format!("-{}", short));
+        let long = self.long.as_ref().map(|long| format!("--{}", long));
+        short.into_iter().chain(long).collect()
+    }
+}
+
+pub fn render(program: &Program, shell: Shell) -> String {
+    let mut nodes = Vec::new();
+    collect(program, &mut Vec::new(), &mut nodes);
+    match shell {
+        Shell::Bash => bash(program, &nodes),
+        Shell::Zsh => zsh(program, &nodes),
+        Shell::Fish => fish(program, &nodes)
+    }
+}
+
+fn collect(program: &Program, path: &mut Vec<String>, nodes: &mut Vec<Node>) {
+    let color_flag = program.accepts_color_flag();
+    let messages = program.catalog();
+    let program = program.borrow();
+    path.push(program.name.clone());
+
+    let children = program.visible_children();
+    let mut commands: Vec<(String, String)> = children
+        .iter()
+        .map(|child| {
+            let child = child.borrow();
+            (child.name.clone(), child.description.clone().unwrap_or_default())
+        })
+        .collect();
+    let aliases = children
+        .iter()
+        .flat_map(|child| {
+            let child = child.borrow();
+            child.aliases.iter().map(|alias| (alias.clone(), child.name.clone())).collect::<Vec<(String, String)>>()
+        })
+        .collect();
+    if program.has_help_command() {
+        commands.push((String::from("help"), String::from(messages.get("help.help"))));
+    }
+
+    let mut options: Vec<Flag> = program
+        .options
+        .iter()
+        .flatten()
+        .map(|option| Flag {
+            short: option.spec.short,
+            long: option.spec.long.clone(),
+            description: option.description.clone(),
+            takes_value: option.takes_value(),
+            choices: option.argument.parser.choices().unwrap_or_default()
+        })
+        .collect();
+    if program.version.is_some() {
+        options.push(Flag {
+            short: Some('V'),
+            long: Some(String::from("version")),
+            description: String::from(messages.get("help.version")),
+            takes_value: false,
+            choices: Vec::new()
+        });
+    }
+    if color_flag {
+        options.push(Flag {
+            short: None,
+            long: Some(String::from("color")),
+            description: String::from(messages.get("help.color")),
+            takes_value: true,
+            choices: ColorChoice::NAMES.iter().map(|name| String::from(*name)).collect()
+        });
+    }
+    options.push(Flag {
+        short: Some('h'),
+        long: Some(String::from("help")),
+        description: String::from(messages.get("help.help")),
+        takes_value: false,
+        choices: Vec::new()
+    });
+
+    let values = program
+        .arguments
+        .iter()
+        .flatten()
+        .flat_map(|argument| argument.parser.choices().unwrap_or_default())
+        .collect();
+
+    nodes.push(Node { path: path.join(" "), commands, aliases, options, values });
+
+    for child in children {
+        collect(child, path, nodes);
+    }
+    path.pop();
+}
+
+/// A shell function name made from the program name.
+fn function_name(program: &Program) -> String {
+    let name: String = program
+        .name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("_{}", name)
+}
+
+/// Quotes `text` for bash and zsh, which treat nothing inside single quotes specially.
+fn sh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Quotes `text` for fish, where `\` and `'` are escaped inside single quotes.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The `case` arms that move from a command's path to a subcommand's path as the words typed so
+/// far are walked. `arm` formats one arm from the `path:word` being matched and the new path.
+fn transitions(nodes: &[Node], arm: impl Fn(&str, &str) -> String) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        for (name, _) in &node.commands {
+            out.push_str(&arm(&format!("{}:{}", node.path, name), &format!("{} {}", node.path, name)));
+        }
+        for (alias, name) in &node.aliases {
+            out.push_str(&arm(&format!("{}:{}", node.path, alias), &format!("{} {}", node.path, name)));
+        }
+    }
+    out
+}
+
+fn bash(program: &Program, nodes: &[Node]) -> String {
+    let function = function_name(program);
+    let mut out = String::new();
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str("    local cur prev word i cmd_path\n");
+    out.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    out.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    let _ = writeln!(out, "    cmd_path={}", sh_quote(&program.name()));
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        word=\"${COMP_WORDS[i]}\"\n");
+    out.push_str("        case \"${cmd_path}:${word}\" in\n");
+    out.push_str(&transitions(nodes, |from, to| format!("            {}) cmd_path={} ;;\n", sh_quote(from), sh_quote(to))));
+    out.push_str("        esac\n");
+    out.push_str("    done\n\n");
+    out.push_str("    case \"${cmd_path}\" in\n");
+    for node in nodes {
+        let _ = writeln!(out, "        {})", sh_quote(&node.path));
+        out.push_str("            case \"${prev}\" in\n");
+        for flag in node.options.iter().filter(|flag| flag.takes_value) {
+            let reply = if flag.choices.is_empty() {
+                String::from("COMPREPLY=($(compgen -f -- \"${cur}\"))")
+            } else {
+                format!("COMPREPLY=($(compgen -W {} -- \"${{cur}}\"))", sh_quote(&flag.choices.join(" ")))
+            };
+            let _ = writeln!(out, "                {}) {}; return ;;", flag.words().join("|"), reply);
+        }
+        out.push_str("            esac\n");
+        let words: Vec<String> = node
+            .options
+            .iter()
+            .flat_map(Flag::words)
+            .chain(node.commands.iter().map(|(name, _)| name.clone()))
+            .chain(node.values.iter().cloned())
+            .collect();
+        let _ = writeln!(out, "            COMPREPLY=($(compgen -W {} -- \"${{cur}}\"))", sh_quote(&words.join(" ")));
+        out.push_str("            ;;\n");
+    }
+    out.push_str("    esac\n");
+    out.push_str("}\n\n");
+    let _ = writeln!(out, "complete -F {} {}", function, program.name());
+    out
+}
+
+fn zsh(program: &Program, nodes: &[Node]) -> String {
+    let function = function_name(program);
+    let mut out = String::new();
+    let _ = writeln!(out, "#compdef {}\n", program.name());
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str("    local word i cmd_path\n");
+    let _ = writeln!(out, "    cmd_path={}", sh_quote(&program.name()));
+    out.push_str("    for ((i = 2; i < CURRENT; i++)); do\n");
+    out.push_str("        word=\"${words[i]}\"\n");
+    out.push_str("        case \"${cmd_path}:${word}\" in\n");
+    out.push_str(&transitions(nodes, |from, to| format!("            {}) cmd_path={} ;;\n", sh_quote(from), sh_quote(to))));
+    out.push_str("        esac\n");
+    out.push_str("    done\n\n");
+    out.push_str("    case \"${cmd_path}\" in\n");
+    for node in nodes {
+        let _ = writeln!(out, "        {})", sh_quote(&node.path));
+        out.push_str("            case \"${words[CURRENT-1]}\" in\n");
+        for flag in node.options.iter().filter(|flag| flag.takes_value) {
+            let reply = if flag.choices.is_empty() {
+                String::from("_files")
+            } else {
+                let choices: Vec<String> = flag.choices.iter().map(|choice| sh_quote(choice)).collect();
+                format!("compadd -- {}", choices.join(" "))
+            };
+            let _ = writeln!(out, "                {}) {}; return ;;", flag.words().join("|"), reply);
+        }
+        out.push_str("            esac\n");
+        if !node.commands.is_empty() {
+            let commands: Vec<String> = node
+                .commands
+                .iter()
+                .map(|(name, description)| sh_quote(&format!("{}:{}", name.replace(':', "\\:"), description)))
+                .collect();
+            let _ = writeln!(out, "            local -a commands=({})", commands.join(" "));
+            out.push_str("            _describe 'command' commands\n");
+        }
+        let options: Vec<String> = node
+            .options
+            .iter()
+            .flat_map(|flag| {
+                flag.words()
+                    .into_iter()
+                    .map(|word| sh_quote(&format!("{}:{}", word, flag.description)))
+                    .collect::<Vec<String>>()
+            })
+            .collect();
+        let _ = writeln!(out, "            local -a options=({})", options.join(" "));
+        out.push_str("            _describe 'option' options\n");
+        if !node.values.is_empty() {
+            let values: Vec<String> = node.values.iter().map(|value| sh_quote(value)).collect();
+            let _ = writeln!(out, "            compadd -- {}", values.join(" "));
+        }
+        out.push_str("            ;;\n");
+    }
+    out.push_str("    esac\n");
+    out.push_str("}\n\n");
+    let _ = writeln!(out, "{} \"$@\"", function);
+    out
+}
+
+fn fish(program: &Program, nodes: &[Node]) -> String {
+    let function = format!("_{}_path", function_name(program));
+    let mut out = String::new();
+    let _ = writeln!(out, "function {}", function);
+    let _ = writeln!(out, "    set -l cmd_path {}", fish_quote(&program.name()));
+    out.push_str("    for word in (commandline -opc)[2..-1]\n");
+    out.push_str("        switch \"$cmd_path:$word\"\n");
+    out.push_str(&transitions(nodes, |from, to| {
+        format!("            case {}\n                set cmd_path {}\n", fish_quote(from), fish_quote(to))
+    }));
+    out.push_str("        end\n");
+    out.push_str("    end\n");
+    out.push_str("    echo $cmd_path\n");
+    out.push_str("end\n\n");
+    let _ = writeln!(out, "complete -c {} -f", program.name());
+    for node in nodes {
+        let condition = fish_quote(&format!("test ({}) = {}", function, fish_quote(&node.path)));
+        let prefix = format!("complete -c {} -n {}", program.name(), condition);
+        for (name, description) in &node.commands {
+            let _ = writeln!(out, "{} -a {} -d {}", prefix, fish_quote(name), fish_quote(description));
+        }
+        for flag in &node.options {
+            let mut line = prefix.clone();
+            if let Some(short) = flag.short {
+                let _ = write!(line, " -s {}", short);
+            }
+            if let Some(long) = &flag.long {
+                let _ = write!(line, " -l {}", long);
+            }
+            if flag.takes_value {
+                line.push_str(" -r");
+            }
+            if !flag.choices.is_empty() {
+                let _ = write!(line, " -xa {}", fish_quote(&flag.choices.join(" ")));
+            }
+            let _ = writeln!(out, "{} -d {}", line, fish_quote(&flag.description));
+        }
+        if !node.values.is_empty() {
+            let _ = writeln!(out, "{} -a {}", prefix, fish_quote(&node.values.join(" ")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::messages::Catalog;
+    use crate::examples::oop::value::EnumParser;
+    use crate::examples::oop::{ProgramArgument, ProgramOption};
+
+    fn program() -> Program {
+        let shells = || EnumParser(vec![String::from("bash"), String::from("zsh")]);
+        Program::new(String::from("tool"))
+            .option(ProgramOption::new(String::from("-s, --shell <name>"), String::from("the shell")).parser(shells()))
+            .add_command(
+                Program::new(String::from("lists"))
+                    .description(String::from("linked lists"))
+                    .add_command(Program::new(String::from("bench")).description(String::from("it's fast")))
+            )
+            .add_command(Program::new(String::from("secret")).hidden())
+            .add_command(
+                Program::new(String::from("completions"))
+                    .alias(String::from("comp"))
+                    .argument(ProgramArgument::new(String::from("<shell>"), String::from("the shell")).parser(shells()))
+            )
+    }
+
+    #[test]
+    fn parses_shell_names() {
+        assert_eq!("zsh".parse::<Shell>(), Ok(Shell::Zsh));
+        assert!("powershell".parse::<Shell>().is_err());
+    }
+
+    #[test]
+    fn bash_covers_the_tree() {
+        let script = render(&program(), Shell::Bash);
+        assert!(script.contains("            'tool:lists') cmd_path='tool lists' ;;\n"));
+        assert!(script.contains("            'tool lists:bench') cmd_path='tool lists bench' ;;\n"));
+        assert!(script.contains("                -s|--shell) COMPREPLY=($(compgen -W 'bash zsh' -- \"${cur}\")); return ;;\n"));
+        assert!(script.contains("        'tool completions')\n"));
+        assert!(script.contains("COMPREPLY=($(compgen -W '-h --help bash zsh' -- \"${cur}\"))"));
+        assert!(script.contains("COMPREPLY=($(compgen -W '-s --shell -h --help completions lists help' -- \"${cur}\"))"));
+        assert!(script.contains("            'tool:comp') cmd_path='tool completions' ;;\n"));
+        assert!(!script.contains("secret"));
+        assert!(script.ends_with("complete -F _tool tool\n"));
+    }
+
+    #[test]
+    fn zsh_describes_commands() {
+        let script = render(&program(), Shell::Zsh);
+        assert!(script.starts_with("#compdef tool\n"));
+        assert!(script.contains("local -a commands=('bench:it'\\''s fast' 'help:display help for command')"));
+        assert!(script.contains("                -s|--shell) compadd -- 'bash' 'zsh'; return ;;\n"));
+        assert!(script.ends_with("_tool \"$@\"\n"));
+    }
+
+    #[test]
+    fn fish_completes_per_path() {
+        let script = render(&program(), Shell::Fish);
+        assert!(script.contains("            case 'tool lists:bench'\n                set cmd_path 'tool lists bench'\n"));
+        assert!(script.contains("complete -c tool -n 'test (__tool_path) = \\'tool lists\\'' -a 'bench' -d 'it\\'s fast'\n"));
+        assert!(script.contains("complete -c tool -n 'test (__tool_path) = \\'tool\\'' -s s -l shell -r -xa 'bash zsh' -d 'the shell'\n"));
+    }
+
+    #[test]
+    fn describes_built_in_flags_from_the_catalog() {
+        let french = Catalog::new("fr").message("help.help", "afficher l'aide de la commande");
+        let script = render(&program().messages(french), Shell::Fish);
+        assert!(script.contains("complete -c tool -n 'test (__tool_path) = \\'tool\\'' -a 'help' -d 'afficher l\\'aide de la commande'\n"));
+        assert!(script.contains("-s h -l help -d 'afficher l\\'aide de la commande'\n"));
+        assert!(!script.contains("display help for command"));
+    }
+}
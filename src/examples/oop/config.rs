@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Values read from a config file, looked up by `section.key`.
+///
+/// The format is the part of INI and TOML they have in common:
+///
+/// ```text
+/// # comments start with '#' or ';'
+/// verbose = true
+///
+/// [lists.bench]
+/// runs = 3
+/// name = "quoted \"strings\" can use escapes"
+/// path = 'literal strings cannot'
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    values: HashMap<String, String>
+}
+
+/// A line of a config file that could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub reason: String
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.reason)
+    }
+}
+
+/// Where the effective value of an option came from, in order of precedence.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueSource {
+    CommandLine,
+    Env(String),
+    Config(String),
+    Default
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSource::CommandLine => write!(f, "command line"),
+            ValueSource::Env(name) => write!(f, "env {}", name),
+            ValueSource::Config(key) => write!(f, "config {}", key),
+            ValueSource::Default => write!(f, "default")
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file at `path`. A file that does not exist is an empty config, so
+    /// programs can name a config file without requiring one.
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|(line, reason)| ConfigError {
+                path: path.to_path_buf(),
+                line,
+                reason
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(error) => Err(ConfigError { path: path.to_path_buf(), line: 0, reason: error.to_string() })
+        }
+    }
+
+    /// Parses the text of a config file. Errors carry the 1-based line they were found on.
+    pub fn parse(text: &str) -> Result<Config, (usize, String)> {
+        let mut values = HashMap::new();
+        let mut section = String::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .strip_suffix(']')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty() && name.split('.').all(is_bare_key))
+                    .ok_or_else(|| (number, format!("invalid section header '{}'", line)))?;
+                section = String::from(name);
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| (number, format!("expected 'key = value', found '{}'", line)))?;
+            let key = key.trim();
+            if !is_bare_key(key) {
+                return Err((number, format!("invalid key '{}'", key)));
+            }
+            let value = parse_value(value.trim()).map_err(|reason| (number, reason))?;
+            let key = if section.is_empty() { String::from(key) } else { format!("{}.{}", section, key) };
+            values.insert(key, value);
+        }
+        Ok(Config { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Reads a value: a `"basic"` string with escapes, a `'literal'` string, or bare text up to an
+/// optional trailing comment.
+fn parse_value(text: &str) -> Result<String, String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return trailing(chars.as_str()).map(|_| value),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    other => return Err(format!("invalid escape '\\{}'", other.map(String::from).unwrap_or_default()))
+                },
+                c => value.push(c)
+            }
+        }
+        return Err(String::from("unterminated string"));
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        let (value, rest) = rest.split_once('\'').ok_or_else(|| String::from("unterminated string"))?;
+        return trailing(rest).map(|_| String::from(value));
+    }
+    let value = match text.split_once('#') {
+        Some((value, _)) => value.trim_end(),
+        None => text
+    };
+    if value.is_empty() {
+        return Err(String::from("missing value"));
+    }
+    Ok(String::from(value))
+}
+
+/// Checks that only whitespace or a comment follows a quoted string.
+fn trailing(rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') || rest.starts_with(';') {
+        return Ok(());
+    }
+    Err(format!("unexpected '{}' after string", rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_sections_and_values() {
+        let config = Config::parse("\
+# top level
+verbose = true
+name = \"say \\\"hi\\\"\" # comment
+
+[lists.bench]
+runs = 3 # times
+path = 'C:\\temp'
+").unwrap();
+        assert_eq!(config.get("verbose"), Some("true"));
+        assert_eq!(config.get("name"), Some("say \"hi\""));
+        assert_eq!(config.get("lists.bench.runs"), Some("3"));
+        assert_eq!(config.get("lists.bench.path"), Some("C:\\temp"));
+        assert_eq!(config.get("runs"), None);
+    }
+
+    #[test]
+    fn reports_the_failing_line() {
+        assert_eq!(Config::parse("a = 1\nb\n").unwrap_err(), (2, String::from("expected 'key = value', found 'b'")));
+        assert_eq!(Config::parse("[lists\n").unwrap_err().0, 1);
+        assert_eq!(Config::parse("a = \"open\n").unwrap_err(), (1, String::from("unterminated string")));
+        assert_eq!(Config::parse("a = 'x' y\n").unwrap_err(), (1, String::from("unexpected 'y' after string")));
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        assert_eq!(Config::load(Path::new("/does/not/exist.toml")), Ok(Config::default()));
+    }
+}
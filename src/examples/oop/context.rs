@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::fmt::Arguments;
+use std::rc::Rc;
+
+use crate::examples::logger::Logger;
+use super::value::Matches;
+use super::{ProgramArgument, ProgramOption};
+
+/// What an action is called with: the values given to its command, the options of the commands
+/// above it and somewhere to write output to.
+pub struct Context {
+    pub(super) path: Vec<String>,
+    pub(super) arguments: Vec<ProgramArgument>,
+    pub(super) options: Vec<ProgramOption>,
+    pub(super) matches: Matches,
+    pub(super) globals: Matches,
+    pub(super) output: Rc<RefCell<dyn Logger>>
+}
+
+impl Context {
+    /// The names of the commands from the root down to the one being run.
+    #[allow(dead_code)]
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    #[allow(dead_code)]
+    pub fn arguments(&self) -> &[ProgramArgument] {
+        &self.arguments
+    }
+
+    /// The command's own options. Options inherited from its ancestors are in `globals`.
+    #[allow(dead_code)]
+    pub fn options(&self) -> &[ProgramOption] {
+        &self.options
+    }
+
+    /// The typed values of the command's own arguments and options.
+    pub fn matches(&self) -> &Matches {
+        &self.matches
+    }
+
+    /// The command's values, for a `pre_action` hook to change before the action sees them.
+    #[allow(dead_code)]
+    pub fn matches_mut(&mut self) -> &mut Matches {
+        &mut self.matches
+    }
+
+    /// The typed values of the options declared on the command's ancestors, which can be given
+    /// before or after the command name, as in `root --verbose lists` or `root lists --verbose`.
+    #[allow(dead_code)]
+    pub fn globals(&self) -> &Matches {
+        &self.globals
+    }
+
+    /// The inherited option values, for a `pre_action` hook to change before the action sees them.
+    #[allow(dead_code)]
+    pub fn globals_mut(&mut self) -> &mut Matches {
+        &mut self.globals
+    }
+
+    /// Replaces where `print` writes to for the rest of this run, e.g. from a `pre_action` hook
+    /// that sets up logging.
+    #[allow(dead_code)]
+    pub fn set_output(&mut self, logger: impl Logger + 'static) {
+        self.output = Rc::new(RefCell::new(logger));
+    }
+
+    /// Writes to the program's output, set with `Program::output`. Use it instead of `println!` so
+    /// tests can capture what an action prints.
+    #[allow(dead_code)]
+    pub fn print(&self, value: &Arguments<'_>) {
+        self.output.borrow_mut().print(value);
+    }
+}
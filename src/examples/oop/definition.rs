@@ -0,0 +1,439 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::flags::FlagSpec;
+use super::json::{Json, JsonError};
+use super::style::ColorChoice;
+use super::value::{BoolParser, EnumParser, FloatParser, IntegerParser, PathParser, StringParser, ValueParser};
+use super::{GroupRule, Program, ProgramArgument, ProgramOption, Repeat};
+
+/// Why a JSON definition could not be loaded as a `Program`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DefinitionError {
+    /// The text is not JSON.
+    Syntax(JsonError),
+    /// The JSON does not describe a program. `path` is where in it, such as
+    /// `commands[0].options[1].flags`.
+    Invalid { path: String, reason: String }
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionError::Syntax(error) => write!(f, "Invalid JSON at {}", error),
+            DefinitionError::Invalid { path, reason } if path.is_empty() => write!(f, "Invalid definition: {}", reason),
+            DefinitionError::Invalid { path, reason } => write!(f, "Invalid definition at {}: {}", path, reason)
+        }
+    }
+}
+
+impl std::error::Error for DefinitionError {}
+
+/// The definition of `program` and every subcommand below it, hidden ones included. Parsers are
+/// given by their `ValueParser::kind`, and are `null` when they have none; actions, hooks and
+/// validators are Rust code and are left out.
+pub fn to_json(program: &Program) -> Json {
+    let data = program.borrow();
+    let mut children: Vec<&Program> = data.children.values().collect();
+    children.sort_by_key(|child| child.name());
+    let groups = data.groups.iter().map(|(name, rule)| {
+        object(vec![("name", Json::String(name.clone())), ("rule", Json::String(String::from(rule_name(*rule))))])
+    });
+
+    object(vec![
+        ("name", Json::String(data.name.clone())),
+        ("aliases", strings(&data.aliases)),
+        ("description", Json::string_or_null(data.description.as_deref())),
+        ("version", Json::string_or_null(data.version.as_deref())),
+        ("hidden", Json::Bool(data.hidden)),
+        ("external_subcommands", Json::Bool(data.external_subcommands)),
+        ("response_files", Json::Bool(data.response_files)),
+        ("no_prompts", Json::Bool(data.no_prompts)),
+        ("color", Json::String(String::from(data.color.name()))),
+        ("color_flag", Json::Bool(data.color_flag)),
+        ("config_file", Json::string_or_null(data.config_file.as_ref().and_then(|path| path.to_str()))),
+        ("groups", Json::Array(groups.collect())),
+        ("arguments", Json::Array(data.arguments.iter().flatten().map(argument_to_json).collect())),
+        ("options", Json::Array(data.options.iter().flatten().map(option_to_json).collect())),
+        ("commands", Json::Array(children.into_iter().map(to_json).collect()))
+    ])
+}
+
+fn argument_to_json(argument: &ProgramArgument) -> Json {
+    object(vec![
+        ("name", Json::String(argument.name.clone())),
+        ("description", Json::String(argument.description.clone())),
+        ("default", Json::string_or_null(Some(argument.default.as_str()).filter(|default| !default.is_empty()))),
+        ("type", Json::string_or_null(argument.parser.kind())),
+        ("choices", argument.parser.choices().map_or(Json::Null, |choices| strings(&choices))),
+        ("password", Json::Bool(argument.password)),
+        ("confirm", Json::Bool(argument.confirm))
+    ])
+}
+
+fn option_to_json(option: &ProgramOption) -> Json {
+    let parser = &option.argument.parser;
+    let repeat = match option.repeat {
+        Repeat::Last => "last",
+        Repeat::Count => "count",
+        Repeat::Append => "append"
+    };
+    object(vec![
+        ("flags", Json::String(option.flags.clone())),
+        ("description", Json::String(option.description.clone())),
+        // A `--no-` flag defaults to on by itself.
+        ("default", Json::string_or_null(Some(option.default.as_str()).filter(|default| !default.is_empty() && !option.spec.negate))),
+        ("preset", Json::string_or_null(option.preset.as_deref())),
+        ("type", Json::string_or_null(parser.kind())),
+        ("choices", parser.choices().map_or(Json::Null, |choices| strings(&choices))),
+        ("env", Json::string_or_null(option.env.as_deref())),
+        ("config", Json::string_or_null(option.config_key.as_deref())),
+        ("repeat", Json::String(String::from(repeat))),
+        ("delimiter", Json::string_or_null(option.delimiter.map(String::from).as_deref())),
+        ("conflicts", strings(&option.conflicts)),
+        ("requires", strings(&option.requires)),
+        ("group", Json::string_or_null(option.group.as_deref()))
+    ])
+}
+
+/// Builds the program `json` describes, in the format `to_json` writes. Keys other than `name`
+/// can be left out, and keys that are not part of the format are ignored.
+pub fn from_json(json: &Json) -> Result<Program, DefinitionError> {
+    let name = required_string(json, "", "name")?;
+    let program = Program::new(name);
+    load(&program, json, "")?;
+    Ok(program)
+}
+
+/// Configures `program`, which is already in its parent's tree so its options can join the
+/// groups of its ancestors, then adds its subcommands.
+fn load(program: &Program, json: &Json, path: &str) -> Result<(), DefinitionError> {
+    if !matches!(json, Json::Object(_)) {
+        return Err(invalid(path, "expected an object"));
+    }
+    let mut program = program.clone();
+    for alias in string_list(json, path, "aliases")? {
+        program = program.alias(alias);
+    }
+    if let Some(description) = optional_string(json, path, "description")? {
+        program = program.description(description);
+    }
+    if let Some(version) = optional_string(json, path, "version")? {
+        program = program.version(version);
+    }
+    if flag(json, path, "hidden")? {
+        program = program.hidden();
+    }
+    if flag(json, path, "external_subcommands")? {
+        program = program.external_subcommands();
+    }
+    if flag(json, path, "response_files")? {
+        program = program.response_files();
+    }
+    if flag(json, path, "no_prompts")? {
+        program = program.no_prompts();
+    }
+    if let Some(color) = optional_string(json, path, "color")? {
+        let color = color.parse::<ColorChoice>().map_err(|reason| invalid(&join(path, "color"), &reason))?;
+        program = program.color(color);
+    }
+    if flag(json, path, "color_flag")? {
+        program = program.color_flag();
+    }
+    if let Some(config_file) = optional_string(json, path, "config_file")? {
+        program = program.config_file(PathBuf::from(config_file));
+    }
+    for (index, group) in list(json, path, "groups")?.iter().enumerate() {
+        let at = format!("{}[{}]", join(path, "groups"), index);
+        let name = required_string(group, &at, "name")?;
+        let rule = match required_string(group, &at, "rule")?.as_str() {
+            "exactly_one" => GroupRule::ExactlyOne,
+            "at_least_one" => GroupRule::AtLeastOne,
+            rule => return Err(invalid(&join(&at, "rule"), &format!("unknown rule '{}'", rule)))
+        };
+        program = program.group(name, rule);
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for (index, argument) in list(json, path, "arguments")?.iter().enumerate() {
+        let at = format!("{}[{}]", join(path, "arguments"), index);
+        let argument = argument_from_json(argument, &at)?;
+        names.push(argument.name.clone());
+        if !super::macros::arguments_in_order(&names.iter().map(String::as_str).collect::<Vec<&str>>()) {
+            return Err(invalid(&at, "a required argument cannot follow an optional one, nor anything a variadic one"));
+        }
+        program = program.argument(argument);
+    }
+    for (index, option) in list(json, path, "options")?.iter().enumerate() {
+        let at = format!("{}[{}]", join(path, "options"), index);
+        let option = option_from_json(option, &at)?;
+        if let Some(group) = option.group.as_ref().filter(|group| program.group_rule(group).is_none()) {
+            return Err(invalid(&join(&at, "group"), &format!("the group '{}' is not declared", group)));
+        }
+        if option.spec.long.as_deref() == Some("color") && program.accepts_color_flag() {
+            return Err(invalid(&join(&at, "flags"), "--color is taken by the color_flag of the program"));
+        }
+        program = program.option(option);
+    }
+
+    for (index, command) in list(json, path, "commands")?.iter().enumerate() {
+        let at = format!("{}[{}]", join(path, "commands"), index);
+        let name = required_string(command, &at, "name")?;
+        if program.borrow().children.contains_key(&name) {
+            return Err(invalid(&join(&at, "name"), &format!("there is already a command '{}'", name)));
+        }
+        load(&program.command(name), command, &at)?;
+    }
+    Ok(())
+}
+
+fn argument_from_json(json: &Json, path: &str) -> Result<ProgramArgument, DefinitionError> {
+    let name = required_string(json, path, "name")?;
+    if !(name.starts_with('<') && name.ends_with('>') || name.starts_with('[') && name.ends_with(']')) {
+        return Err(invalid(&join(path, "name"), &format!("'{}' is neither <required> nor [optional]", name)));
+    }
+    let mut argument = ProgramArgument::new(name, optional_string(json, path, "description")?.unwrap_or_default());
+    if let Some(default) = optional_string(json, path, "default")? {
+        argument = argument.default(default);
+    }
+    if let Some(parser) = parser_from_json(json, path)? {
+        argument.parser = parser;
+    }
+    if flag(json, path, "password")? {
+        argument = argument.password();
+    }
+    if flag(json, path, "confirm")? {
+        argument = argument.confirm();
+    }
+    Ok(argument)
+}
+
+fn option_from_json(json: &Json, path: &str) -> Result<ProgramOption, DefinitionError> {
+    let flags = required_string(json, path, "flags")?;
+    FlagSpec::parse(&flags).map_err(|reason| invalid(&join(path, "flags"), &reason))?;
+    let mut option = ProgramOption::new(flags, optional_string(json, path, "description")?.unwrap_or_default());
+    if let Some(default) = optional_string(json, path, "default")? {
+        option = option.default(default);
+    }
+    if let Some(preset) = optional_string(json, path, "preset")? {
+        if !option.has_optional_value() {
+            return Err(invalid(&join(path, "preset"), "only an option with an optional value can have one"));
+        }
+        option = option.preset(preset);
+    }
+    if let Some(parser) = parser_from_json(json, path)? {
+        option.argument.parser = parser;
+    }
+    if let Some(name) = optional_string(json, path, "env")? {
+        option = option.env(name);
+    }
+    if let Some(key) = optional_string(json, path, "config")? {
+        option = option.config(key);
+    }
+    let takes_value = option.takes_value();
+    match optional_string(json, path, "repeat")?.as_deref() {
+        None | Some("last") => (),
+        Some("count") if !takes_value && !option.spec.negate => option = option.count(),
+        Some("append") if takes_value => option = option.append(),
+        Some(repeat @ ("count" | "append")) => {
+            return Err(invalid(&join(path, "repeat"), &format!("'{}' does not suit '{}'", repeat, option.flags)));
+        }
+        Some(repeat) => return Err(invalid(&join(path, "repeat"), &format!("unknown repeat '{}'", repeat)))
+    }
+    if let Some(delimiter) = optional_string(json, path, "delimiter")? {
+        let mut chars = delimiter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(delimiter), None) if takes_value => option = option.delimiter(delimiter),
+            (Some(_), None) => return Err(invalid(&join(path, "delimiter"), "only an option with a value can be split")),
+            _ => return Err(invalid(&join(path, "delimiter"), "expected a single character"))
+        }
+    }
+    for name in string_list(json, path, "conflicts")? {
+        option = option.conflicts_with(name);
+    }
+    for name in string_list(json, path, "requires")? {
+        option = option.requires(name);
+    }
+    if let Some(group) = optional_string(json, path, "group")? {
+        option = option.group(group);
+    }
+    Ok(option)
+}
+
+/// The built-in parser named by `type`, with `choices` for `enum`. `None` keeps the parser the
+/// argument or option starts with.
+fn parser_from_json(json: &Json, path: &str) -> Result<Option<Rc<dyn ValueParser>>, DefinitionError> {
+    let parser: Rc<dyn ValueParser> = match optional_string(json, path, "type")?.as_deref() {
+        None => return Ok(None),
+        Some("string") => Rc::new(StringParser),
+        Some("integer") => Rc::new(IntegerParser),
+        Some("float") => Rc::new(FloatParser),
+        Some("bool") => Rc::new(BoolParser),
+        Some("path") => Rc::new(PathParser),
+        Some("enum") => {
+            let choices = string_list(json, path, "choices")?;
+            if choices.is_empty() {
+                return Err(invalid(&join(path, "choices"), "an enum needs choices"));
+            }
+            Rc::new(EnumParser(choices))
+        }
+        Some(kind) => return Err(invalid(&join(path, "type"), &format!("unknown type '{}'", kind)))
+    };
+    Ok(Some(parser))
+}
+
+fn rule_name(rule: GroupRule) -> &'static str {
+    match rule {
+        GroupRule::ExactlyOne => "exactly_one",
+        GroupRule::AtLeastOne => "at_least_one"
+    }
+}
+
+fn object(entries: Vec<(&str, Json)>) -> Json {
+    Json::Object(entries.into_iter().map(|(key, value)| (String::from(key), value)).collect())
+}
+
+fn strings(values: &[String]) -> Json {
+    Json::Array(values.iter().cloned().map(Json::String).collect())
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        String::from(key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn invalid(path: &str, reason: &str) -> DefinitionError {
+    DefinitionError::Invalid { path: String::from(path), reason: String::from(reason) }
+}
+
+fn required_string(json: &Json, path: &str, key: &str) -> Result<String, DefinitionError> {
+    optional_string(json, path, key)?.ok_or_else(|| invalid(&join(path, key), "missing"))
+}
+
+/// The string at `key`, or `None` if it is missing or `null`.
+fn optional_string(json: &Json, path: &str, key: &str) -> Result<Option<String>, DefinitionError> {
+    match json.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(text)) => Ok(Some(text.clone())),
+        Some(_) => Err(invalid(&join(path, key), "expected a string"))
+    }
+}
+
+fn flag(json: &Json, path: &str, key: &str) -> Result<bool, DefinitionError> {
+    match json.get(key) {
+        None | Some(Json::Null) => Ok(false),
+        Some(value) => value.as_bool().ok_or_else(|| invalid(&join(path, key), "expected true or false"))
+    }
+}
+
+/// The array at `key`, empty if it is missing or `null`.
+fn list<'a>(json: &'a Json, path: &str, key: &str) -> Result<&'a [Json], DefinitionError> {
+    match json.get(key) {
+        None | Some(Json::Null) => Ok(&[]),
+        Some(value) => value.as_array().ok_or_else(|| invalid(&join(path, key), "expected an array"))
+    }
+}
+
+fn string_list(json: &Json, path: &str, key: &str) -> Result<Vec<String>, DefinitionError> {
+    list(json, path, key)?
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            item.as_str().map(String::from).ok_or_else(|| invalid(&format!("{}[{}]", join(path, key), index), "expected a string"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program() -> Program {
+        let root = Program::new(String::from("tool"))
+            .version(String::from("1.0.0"))
+            .description(String::from("Does \"tool\" things"))
+            .response_files()
+            .color_flag()
+            .group(String::from("format"), GroupRule::ExactlyOne)
+            .option(ProgramOption::new(String::from("-v, --verbose"), String::from("verbosity")).count())
+            .option(ProgramOption::new(String::from("--json"), String::from("json")).group(String::from("format")))
+            .option(
+                ProgramOption::new(String::from("--text"), String::from("text"))
+                    .group(String::from("format"))
+                    .conflicts_with(String::from("verbose"))
+            );
+        root.command(String::from("lists"))
+            .alias(String::from("ls"))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("size")).parser(IntegerParser))
+            .argument(ProgramArgument::new(String::from("[shell]"), String::new()).parser(EnumParser(vec![String::from("bash")])))
+            .option(
+                ProgramOption::new(String::from("-t, --tags <tags>"), String::from("tags"))
+                    .append()
+                    .delimiter(',')
+                    .env(String::from("TAGS"))
+                    .default(String::from("a,b"))
+            )
+            .option(ProgramOption::new(String::from("--no-pager"), String::from("no pager")))
+            .option(ProgramOption::new(String::from("--level [n]"), String::from("level")).preset(String::from("1")))
+            .command(String::from("secret"))
+            .hidden();
+        root
+    }
+
+    #[test]
+    fn loads_what_it_writes() {
+        let json = to_json(&program());
+        let text = json.to_string();
+        let loaded = from_json(&Json::parse(&text).unwrap()).unwrap();
+        assert_eq!(to_json(&loaded).to_string(), text);
+        assert_eq!(loaded.help(), program().help());
+        assert_eq!(loaded.completions(super::super::completion::Shell::Bash), program().completions(super::super::completion::Shell::Bash));
+    }
+
+    #[test]
+    fn describes_each_part() {
+        let json = to_json(&program());
+        let lists = &json.get("commands").and_then(Json::as_array).unwrap()[0];
+        assert_eq!(lists.get("aliases"), Some(&Json::Array(vec![Json::String(String::from("ls"))])));
+        let tags = &lists.get("options").and_then(Json::as_array).unwrap()[0];
+        assert_eq!(tags.get("repeat").and_then(Json::as_str), Some("append"));
+        assert_eq!(tags.get("delimiter").and_then(Json::as_str), Some(","));
+        assert_eq!(tags.get("default").and_then(Json::as_str), Some("a,b"));
+        let size = &lists.get("arguments").and_then(Json::as_array).unwrap()[0];
+        assert_eq!(size.get("type").and_then(Json::as_str), Some("integer"));
+    }
+
+    #[test]
+    fn points_at_invalid_definitions() {
+        let load = |text: &str| from_json(&Json::parse(text).unwrap()).err().map(|error| error.to_string());
+        assert_eq!(load(r#"{"commands": []}"#).as_deref(), Some("Invalid definition at name: missing"));
+        assert_eq!(
+            load(r#"{"name": "t", "commands": [{"name": "a", "options": [{"flags": "-x, --xx, --yy"}]}]}"#).as_deref(),
+            Some("Invalid definition at commands[0].options[0].flags: Invalid long flag '--yy' in '-x, --xx, --yy'")
+        );
+        assert_eq!(
+            load(r#"{"name": "t", "arguments": [{"name": "[a]"}, {"name": "<b>"}]}"#).as_deref(),
+            Some("Invalid definition at arguments[1]: a required argument cannot follow an optional one, nor anything a variadic one")
+        );
+        assert_eq!(
+            load(r#"{"name": "t", "options": [{"flags": "--json", "group": "format"}]}"#).as_deref(),
+            Some("Invalid definition at options[0].group: the group 'format' is not declared")
+        );
+        assert_eq!(
+            load(r#"{"name": "t", "options": [{"flags": "--json", "repeat": "append"}]}"#).as_deref(),
+            Some("Invalid definition at options[0].repeat: 'append' does not suit '--json'")
+        );
+        assert_eq!(
+            load(r#"{"name": "t", "options": [{"flags": "--level <n>", "preset": "1"}]}"#).as_deref(),
+            Some("Invalid definition at options[0].preset: only an option with an optional value can have one")
+        );
+        assert_eq!(
+            load(r#"{"name": "t", "color_flag": true, "commands": [{"name": "a", "options": [{"flags": "--no-color"}]}]}"#).as_deref(),
+            Some("Invalid definition at commands[0].options[0].flags: --color is taken by the color_flag of the program")
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use super::messages::Catalog;
+use super::style::Style;
+
+/// Everything that can go wrong while turning the command line into a call to an action.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    UnknownCommand { name: String, suggestion: Option<String> },
+    UnknownOption { name: String, suggestion: Option<String> },
+    MissingArgument { name: String },
+    UnexpectedArgument { value: String },
+    InvalidValue { kind: String, name: String, value: String, reason: String },
+    /// An option that needs a value was given none, as in `--output` at the end of the line.
+    MissingValue { option: String },
+    /// A flag was given a value, as in `--verbose=yes`.
+    UnexpectedValue { option: String },
+    /// A command that only groups subcommands was run with options but no subcommand.
+    MissingCommand { command: String },
+    NotImplemented { command: String },
+    /// The program's config file could not be read. `line` is 0 when the whole file could not be.
+    InvalidConfig { path: PathBuf, line: usize, reason: String },
+    /// An `@file` argument could not be expanded. `line` is 0 when the whole file could not be
+    /// read, and otherwise the line of `path` with the problem.
+    InvalidResponseFile { path: PathBuf, line: usize, reason: String },
+    /// An argument given to `Program::parse_from` is not valid UTF-8. `value` has the invalid
+    /// bytes replaced.
+    InvalidEncoding { value: String },
+    /// Two options were given that cannot be used together, because one conflicts with the other
+    /// or because both are in a group that allows only one.
+    ConflictingOptions { option: String, other: String },
+    /// An option was given without an option it requires.
+    MissingRequiredOption { option: String, required: String },
+    /// None of the options of a group that needs at least one were given.
+    MissingGroup { group: String, options: Vec<String> },
+    /// An option of the program joins a group that is not declared on its command or above. It
+    /// is a mistake in the program rather than in the arguments, found before they are parsed.
+    UndeclaredGroup { option: String, group: String },
+    /// The command's action returned an error. The process exits with the error's code.
+    ActionFailed { command: String, error: ActionError },
+    /// An external subcommand such as `git-foo` exited with a non-zero code, which is passed on.
+    ExternalFailed { command: String, code: i32 }
+}
+
+impl ParseError {
+    /// The code the process exits with when the error reaches `main`. Every kind of parse error
+    /// has its own, from 2 to 17, so scripts can tell them apart. Failed actions and external
+    /// subcommands pass on their own code, which is never 0 but may fall in that range; see
+    /// `ActionError::code`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ParseError::UnknownCommand { .. } => 2,
+            ParseError::UnknownOption { .. } => 3,
+            ParseError::MissingArgument { .. } => 4,
+            ParseError::UnexpectedArgument { .. } => 5,
+            ParseError::InvalidValue { .. } => 6,
+            ParseError::MissingValue { .. } => 7,
+            ParseError::UnexpectedValue { .. } => 8,
+            ParseError::MissingCommand { .. } => 9,
+            ParseError::NotImplemented { .. } => 10,
+            ParseError::InvalidConfig { .. } => 11,
+            ParseError::ConflictingOptions { .. } => 12,
+            ParseError::MissingRequiredOption { .. } => 13,
+            ParseError::MissingGroup { .. } => 14,
+            ParseError::InvalidResponseFile { .. } => 15,
+            ParseError::InvalidEncoding { .. } => 16,
+            ParseError::UndeclaredGroup { .. } => 17,
+            ParseError::ActionFailed { error, .. } if error.code == 0 => 1,
+            ParseError::ActionFailed { error, .. } => error.code,
+            ParseError::ExternalFailed { code, .. } => *code
+        }
+    }
+}
+
+impl ParseError {
+    /// The message for the error, in the language of `messages`.
+    pub fn message(&self, messages: &Catalog) -> String {
+        self.styled_message(messages, Style::default())
+    }
+
+    /// The message for the error with any suggestion highlighted by `style`.
+    pub fn styled_message(&self, messages: &Catalog, style: Style) -> String {
+        let message = |id: &str, values: &[(&str, &dyn fmt::Display)]| messages.format(id, values);
+        match self {
+            ParseError::UnknownCommand { name, suggestion } => {
+                message("error.unknown_command", &[("name", name)]) + &suggest_message(messages, suggestion, style)
+            }
+            ParseError::UnknownOption { name, suggestion } => {
+                message("error.unknown_option", &[("name", name)]) + &suggest_message(messages, suggestion, style)
+            }
+            ParseError::MissingArgument { name } => message("error.missing_argument", &[("name", name)]),
+            ParseError::UnexpectedArgument { value } => message("error.unexpected_argument", &[("value", value)]),
+            ParseError::InvalidValue { kind, name, value, reason } => message(
+                "error.invalid_value",
+                &[("value", value), ("kind", &messages.get(&format!("kind.{}", kind))), ("name", name), ("reason", reason)]
+            ),
+            ParseError::MissingValue { option } => message("error.missing_value", &[("option", option)]),
+            ParseError::UnexpectedValue { option } => message("error.unexpected_value", &[("option", option)]),
+            ParseError::MissingCommand { command } => message("error.missing_command", &[("command", command)]),
+            ParseError::NotImplemented { command } => message("error.not_implemented", &[("command", command)]),
+            ParseError::InvalidConfig { path, line: 0, reason } => {
+                message("error.unreadable_config", &[("path", &path.display()), ("reason", reason)])
+            }
+            ParseError::InvalidConfig { path, line, reason } => {
+                message("error.invalid_config", &[("path", &path.display()), ("line", line), ("reason", reason)])
+            }
+            ParseError::InvalidResponseFile { path, line: 0, reason } => {
+                message("error.unreadable_response_file", &[("path", &path.display()), ("reason", reason)])
+            }
+            ParseError::InvalidResponseFile { path, line, reason } => {
+                message("error.invalid_response_file", &[("path", &path.display()), ("line", line), ("reason", reason)])
+            }
+            ParseError::InvalidEncoding { value } => message("error.invalid_encoding", &[("value", value)]),
+            ParseError::ConflictingOptions { option, other } => {
+                message("error.conflicting_options", &[("option", option), ("other", other)])
+            }
+            ParseError::MissingRequiredOption { option, required } => {
+                message("error.missing_required_option", &[("option", option), ("required", required)])
+            }
+            ParseError::MissingGroup { group, options } => {
+                message("error.missing_group", &[("options", &options.join("', '")), ("group", group)])
+            }
+            ParseError::UndeclaredGroup { option, group } => message("error.undeclared_group", &[("option", option), ("group", group)]),
+            ParseError::ActionFailed { command, error } => {
+                message("error.action_failed", &[("command", command), ("message", &error.message)])
+            }
+            ParseError::ExternalFailed { command, code } => message("error.external_failed", &[("command", command), ("code", code)])
+        }
+    }
+}
+
+/// The message in English.
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(&Catalog::english()))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// What an action returns when it fails: a message for the user and the code to exit with, 1
+/// unless it is set with `code`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionError {
+    pub message: String,
+    pub code: i32
+}
+
+impl ActionError {
+    pub fn new(message: String) -> Self {
+        Self { message, code: 1 }
+    }
+
+    /// Sets the code to exit with. A failure must not exit with 0, so 0 becomes 1. Parse errors
+    /// exit with 2 to 17, so a code of 1 or from 64 up keeps scripts able to tell the two apart.
+    #[allow(dead_code)]
+    pub fn code(mut self, code: i32) -> ActionError {
+        self.code = if code == 0 { 1 } else { code };
+        self
+    }
+}
+
+impl From<String> for ActionError {
+    fn from(message: String) -> Self {
+        ActionError::new(message)
+    }
+}
+
+impl From<&str> for ActionError {
+    fn from(message: &str) -> Self {
+        ActionError::new(String::from(message))
+    }
+}
+
+impl From<std::io::Error> for ActionError {
+    fn from(error: std::io::Error) -> Self {
+        ActionError::new(error.to_string())
+    }
+}
+
+/// What an action returns. An `Err` ends the program with the error's exit code.
+pub type ActionResult = Result<(), ActionError>;
+
+fn suggest_message(messages: &Catalog, suggestion: &Option<String>, style: Style) -> String {
+    match suggestion {
+        Some(suggestion) => messages.format("error.suggestion", &[("suggestion", &style.suggestion(suggestion))]),
+        None => String::new()
+    }
+}
+
+/// The candidate closest to `name`, if it is close enough to be a likely typo: at most two edits
+/// away, and fewer edits than `name` has characters.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2 && *distance < name.chars().count())
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| String::from(candidate))
+}
+
+/// The Damerau-Levenshtein distance between `a` and `b`: the number of insertions, deletions,
+/// substitutions and swaps of neighbouring characters it takes to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("lists", "lists"), 0);
+        assert_eq!(edit_distance("lsits", "lists"), 1);
+        assert_eq!(edit_distance("list", "lists"), 1);
+        assert_eq!(edit_distance("bench", "branch"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn suggests_the_closest_candidate() {
+        let candidates = ["console_log", "oop", "get_home_dir"];
+        assert_eq!(suggest("consol_log", candidates), Some(String::from("console_log")));
+        assert_eq!(suggest("opo", candidates), Some(String::from("oop")));
+        assert_eq!(suggest("xyz", candidates), None);
+    }
+
+    #[test]
+    fn failed_actions_never_exit_with_zero() {
+        assert_eq!(ActionError::from("x").code(0).code, 1);
+        assert_eq!(ActionError::from("x").code(70).code, 70);
+        let error = ActionError { message: String::from("x"), code: 0 };
+        assert_eq!(ParseError::ActionFailed { command: String::from("run"), error }.exit_code(), 1);
+    }
+
+    #[test]
+    fn leaves_the_line_out_for_unreadable_files() {
+        let error = |line: usize| ParseError::InvalidResponseFile { path: PathBuf::from("args.txt"), line, reason: String::from("gone") };
+        assert_eq!(error(0).to_string(), "Cannot read response file args.txt: gone");
+        assert_eq!(error(3).to_string(), "Invalid response file args.txt:3: gone");
+        let error = ParseError::InvalidConfig { path: PathBuf::from("config.toml"), line: 0, reason: String::from("gone") };
+        assert_eq!(error.to_string(), "Cannot read config file config.toml: gone");
+    }
+
+    #[test]
+    fn displays_suggestion() {
+        let error = ParseError::UnknownCommand { name: String::from("opo"), suggestion: Some(String::from("oop")) };
+        assert_eq!(error.to_string(), "Unknown command 'opo' (Did you mean 'oop'?)");
+    }
+}
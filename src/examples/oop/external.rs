@@ -0,0 +1,73 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::error::{ActionError, ParseError};
+use super::Stream;
+
+/// The first executable called `name` in the directories of `path`, a list in the format of
+/// `$PATH`.
+pub fn find(name: &str, path: &OsStr) -> Option<PathBuf> {
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(path)
+        .map(|dir| dir.join(&file))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Runs `program` with `args` and waits for it. It shares this process's stdin, and its stdout and
+/// stderr unless `stdout` or `stderr` are given, which then get what it wrote to them. A non-zero
+/// exit becomes an `ExternalFailed` error carrying its code.
+pub fn run(program: &Path, args: &[String], stdout: Option<Stream>, stderr: Option<Stream>) -> Result<(), ParseError> {
+    let command = program.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let failed = |error: std::io::Error| ParseError::ActionFailed { command: command.clone(), error: ActionError::from(error) };
+    let mut child = Command::new(program);
+    child.args(args);
+    if stdout.is_some() {
+        child.stdout(Stdio::piped());
+    }
+    if stderr.is_some() {
+        child.stderr(Stdio::piped());
+    }
+    let output = child.spawn().and_then(|child| child.wait_with_output()).map_err(failed)?;
+    for (writer, bytes) in [(stdout, &output.stdout), (stderr, &output.stderr)] {
+        if let Some(writer) = writer {
+            writer.borrow_mut().write_all(bytes).map_err(failed)?;
+        }
+    }
+    match output.status.code() {
+        Some(0) => Ok(()),
+        // A process killed by a signal has no code.
+        code => Err(ParseError::ExternalFailed { command: command.clone(), code: code.unwrap_or(1) })
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::examples::oop::testing::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn finds_only_executables() {
+        let dir = TempDir::new("find");
+        std::fs::write(dir.join("tool-run"), "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(dir.join("tool-run"), std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("tool-data"), "").unwrap();
+        let path = std::env::join_paths([Path::new("/nonexistent"), dir.path()]).unwrap();
+
+        assert_eq!(find("tool-run", &path), Some(dir.join("tool-run")));
+        assert_eq!(find("tool-data", &path), None);
+        assert_eq!(find("tool-missing", &path), None);
+    }
+}
@@ -0,0 +1,107 @@
+/// The parsed form of a `ProgramOption`'s flags, e.g. `"-v, --verbose <level>"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlagSpec {
+    pub short: Option<char>,
+    pub long: Option<String>,
+    pub placeholder: Option<Placeholder>,
+    /// Set when the long flag was written as `--no-<flag>`, which makes the option default to on.
+    pub negate: bool
+}
+
+/// The value placeholder of an option: `<value>` is required and `[value]` is optional.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    pub required: bool
+}
+
+impl FlagSpec {
+    pub fn parse(flags: &str) -> Result<FlagSpec, String> {
+        let mut spec = FlagSpec { short: None, long: None, placeholder: None, negate: false };
+
+        for word in flags.split(|c: char| c == ',' || c.is_whitespace()).filter(|word| !word.is_empty()) {
+            if spec.placeholder.is_some() {
+                return Err(format!("Unexpected '{}' after the value placeholder in '{}'", word, flags));
+            }
+            if let Some(long) = word.strip_prefix("--") {
+                if spec.long.is_some() || long.is_empty() {
+                    return Err(format!("Invalid long flag '{}' in '{}'", word, flags));
+                }
+                match long.strip_prefix("no-") {
+                    Some(negated) => {
+                        spec.long = Some(String::from(negated));
+                        spec.negate = true;
+                    }
+                    None => spec.long = Some(String::from(long))
+                }
+            } else if let Some(short) = word.strip_prefix('-') {
+                let mut chars = short.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if spec.short.is_none() && c != '-' => spec.short = Some(c),
+                    _ => return Err(format!("Invalid short flag '{}' in '{}'", word, flags))
+                }
+            } else if let Some(name) = placeholder_name(word, '<', '>') {
+                spec.placeholder = Some(Placeholder { name: String::from(name), required: true });
+            } else if let Some(name) = placeholder_name(word, '[', ']') {
+                spec.placeholder = Some(Placeholder { name: String::from(name), required: false });
+            } else {
+                return Err(format!("Unexpected '{}' in flags '{}'", word, flags));
+            }
+        }
+
+        if spec.short.is_none() && spec.long.is_none() {
+            return Err(format!("No flag found in '{}'", flags));
+        }
+        if spec.negate && spec.placeholder.is_some() {
+            return Err(format!("Negated flag '{}' cannot take a value", flags));
+        }
+        Ok(spec)
+    }
+
+    /// The name used to look the option up: the long flag if there is one, otherwise the short.
+    pub fn name(&self) -> String {
+        match (&self.long, self.short) {
+            (Some(long), _) => long.clone(),
+            (None, Some(short)) => short.to_string(),
+            (None, None) => String::new()
+        }
+    }
+}
+
+fn placeholder_name(word: &str, open: char, close: char) -> Option<&str> {
+    word.strip_prefix(open)?.strip_suffix(close).filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_short_long_and_value() {
+        let spec = FlagSpec::parse("-v, --verbose <level>").unwrap();
+        assert_eq!(spec.short, Some('v'));
+        assert_eq!(spec.long, Some(String::from("verbose")));
+        assert_eq!(spec.placeholder, Some(Placeholder { name: String::from("level"), required: true }));
+        assert_eq!(spec.name(), "verbose");
+    }
+
+    #[test]
+    fn parses_optional_value_and_negation() {
+        let spec = FlagSpec::parse("--color [when]").unwrap();
+        assert_eq!(spec.short, None);
+        assert_eq!(spec.placeholder, Some(Placeholder { name: String::from("when"), required: false }));
+
+        let spec = FlagSpec::parse("--no-color").unwrap();
+        assert_eq!(spec.long, Some(String::from("color")));
+        assert!(spec.negate);
+    }
+
+    #[test]
+    fn rejects_malformed_flags() {
+        assert!(FlagSpec::parse("").is_err());
+        assert!(FlagSpec::parse("-vv").is_err());
+        assert!(FlagSpec::parse("verbose").is_err());
+        assert!(FlagSpec::parse("-v <a> <b>").is_err());
+        assert!(FlagSpec::parse("--no-color <when>").is_err());
+    }
+}
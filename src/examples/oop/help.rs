@@ -0,0 +1,395 @@
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use super::config::{Config, ValueSource};
+use super::messages::Catalog;
+use super::style::{ColorChoice, Style};
+use super::{GroupRule, Program, ProgramArgument, ProgramData, ProgramOption, Repeat};
+
+pub(super) const HELP_FLAGS: &str = "-h, --help";
+pub(super) const VERSION_FLAGS: &str = "-V, --version";
+pub(super) const COLOR_FLAGS: &str = "--color <when>";
+/// The width help is wrapped to when it is not written to a terminal.
+pub(super) const WIDTH: usize = 80;
+
+/// The width help is wrapped to: `$COLUMNS` when it is set, otherwise the width of the terminal,
+/// otherwise 80. Shells set `$COLUMNS` without exporting it, so it is mostly there to override.
+/// The terminal is only asked once per process.
+pub fn terminal_width() -> usize {
+    static STTY_COLUMNS: OnceLock<Option<usize>> = OnceLock::new();
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| *STTY_COLUMNS.get_or_init(stty_columns))
+        .unwrap_or(WIDTH)
+}
+
+/// The columns `stty size` reports for the terminal on stdin, if stdin is one.
+#[cfg(unix)]
+fn stty_columns() -> Option<usize> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    let output = std::process::Command::new("stty").arg("size").stderr(std::process::Stdio::null()).output().ok()?;
+    let size = String::from_utf8(output.stdout).ok()?;
+    size.split_whitespace().nth(1)?.parse().ok().filter(|columns| *columns > 0)
+}
+
+#[cfg(not(unix))]
+fn stty_columns() -> Option<usize> {
+    None
+}
+
+/// Renders the help for `program`. Options that fall back to an environment variable or `config`
+/// show where their current value comes from. Everything but the names and descriptions the
+/// program was given is in the language of its catalog. Headings and command names are styled
+/// with `style`.
+pub fn render(program: &Program, config: &Config, width: usize, style: Style) -> String {
+    let path = program.path();
+    let groups = program.all_groups();
+    let color_flag = program.accepts_color_flag();
+    let messages = program.catalog();
+    let program = program.borrow();
+    let mut out = messages.format("help.usage", &[("usage", &usage(&program, &path))]);
+    out.push('\n');
+
+    if let Some(description) = &program.description {
+        out.push('\n');
+        for line in wrap(description, width) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+
+    let arguments: Vec<(String, String)> = program
+        .arguments
+        .iter()
+        .flatten()
+        .map(|argument| (argument.key(), annotate(argument, &argument.description, &argument.default, &messages)))
+        .collect();
+
+    let mut options: Vec<(String, String)> = program
+        .options
+        .iter()
+        .flatten()
+        .filter(|option| option.group.is_none())
+        .map(|option| (option.flags.clone(), annotate_option(option, config, &messages)))
+        .collect();
+    if program.version.is_some() {
+        options.push((String::from(VERSION_FLAGS), String::from(messages.get("help.version"))));
+    }
+    if color_flag {
+        options.push((String::from(COLOR_FLAGS), describe_color(&messages)));
+    }
+    options.push((String::from(HELP_FLAGS), String::from(messages.get("help.help"))));
+
+    let mut commands: Vec<(String, String)> = program
+        .visible_children()
+        .into_iter()
+        .map(|child| {
+            let child = child.borrow();
+            let names = std::iter::once(&child.name).chain(&child.aliases).cloned().collect::<Vec<String>>();
+            (names.join("|"), child.description.clone().unwrap_or_default())
+        })
+        .collect();
+    if program.has_help_command() {
+        commands.push((String::from("help [command]"), String::from(messages.get("help.help"))));
+    }
+
+    // Grouped options are listed under their group, after the other options. Only the terms of
+    // the commands section are command names.
+    let mut sections = vec![
+        (String::from(messages.get("help.arguments")), arguments, false),
+        (String::from(messages.get("help.options")), options, false)
+    ];
+    for (group, rule) in groups {
+        let rows = program
+            .options
+            .iter()
+            .flatten()
+            .filter(|option| option.group.as_ref() == Some(&group))
+            .map(|option| (option.flags.clone(), annotate_option(option, config, &messages)))
+            .collect();
+        let rule = messages.get(match rule {
+            GroupRule::ExactlyOne => "rule.exactly_one",
+            GroupRule::AtLeastOne => "rule.at_least_one"
+        });
+        sections.push((messages.format("help.group", &[("group", &capitalize(&group)), ("rule", &rule)]), rows, false));
+    }
+    sections.push((String::from(messages.get("help.commands")), commands, true));
+
+    let column = sections
+        .iter()
+        .flat_map(|(_, rows, _)| rows)
+        .map(|(term, _)| term.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (heading, rows, commands) in sections {
+        if rows.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n{}\n", style.heading(&format!("{}:", heading))));
+        let paint = |term: &str| if commands { style.command(term) } else { String::from(term) };
+        for (term, description) in rows {
+            out.push_str(&row(&term, &description, column, width, paint));
+        }
+    }
+    out
+}
+
+/// The description of `--color`, with its choices.
+pub(super) fn describe_color(messages: &Catalog) -> String {
+    let mut out = String::from(messages.get("help.color"));
+    push(&mut out, messages.format("help.choices", &[("choices", &ColorChoice::NAMES.join(", "))]));
+    out
+}
+
+pub(super) fn usage(program: &ProgramData, path: &[String]) -> String {
+    let mut usage = path.join(" ");
+    usage.push_str(" [options]");
+    for argument in program.arguments.iter().flatten() {
+        usage.push(' ');
+        usage.push_str(&argument.name);
+    }
+    if !program.children.is_empty() {
+        usage.push_str(" [command]");
+    }
+    usage
+}
+
+/// Adds to `describe_option` the value the option currently falls back to, if one is set.
+fn annotate_option(option: &ProgramOption, config: &Config, messages: &Catalog) -> String {
+    let mut out = describe_option(option, messages);
+    if let Some((value, source)) = option.fallback(config, None) {
+        let source = match &source {
+            ValueSource::CommandLine => String::from(messages.get("source.command_line")),
+            ValueSource::Env(name) => messages.format("source.env", &[("name", name)]),
+            ValueSource::Config(key) => messages.format("source.config", &[("key", key)]),
+            ValueSource::Default => String::from(messages.get("source.default"))
+        };
+        push(&mut out, messages.format("help.current", &[("value", &value), ("source", &source)]));
+    }
+    out.trim_start().to_string()
+}
+
+/// Adds to `annotate` the environment variable and config key the option falls back to.
+pub(super) fn describe_option(option: &ProgramOption, messages: &Catalog) -> String {
+    let mut out = annotate(&option.argument, &option.description, &option.default, messages);
+    if option.repeat != Repeat::Last {
+        push(&mut out, String::from(messages.get("help.repeatable")));
+    }
+    if let Some(delimiter) = option.delimiter {
+        push(&mut out, messages.format("help.delimiter", &[("delimiter", &delimiter)]));
+    }
+    if let Some(name) = &option.env {
+        push(&mut out, messages.format("help.env", &[("name", name)]));
+    }
+    if let Some(key) = &option.config_key {
+        push(&mut out, messages.format("help.config", &[("key", key)]));
+    }
+    for name in &option.conflicts {
+        push(&mut out, messages.format("help.conflicts", &[("flag", &flag(name))]));
+    }
+    for name in &option.requires {
+        push(&mut out, messages.format("help.requires", &[("flag", &flag(name))]));
+    }
+    out.trim_start().to_string()
+}
+
+/// Appends a note to a description, after a space.
+fn push(description: &mut String, note: String) {
+    description.push(' ');
+    description.push_str(&note);
+}
+
+/// The flag for an option name: `--name`, or `-n` for a single letter.
+fn flag(name: &str) -> String {
+    if name.chars().count() == 1 {
+        format!("-{}", name)
+    } else {
+        format!("--{}", name)
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Adds the accepted choices and the default, if there are any, to a description.
+pub(super) fn annotate(argument: &ProgramArgument, description: &str, default: &str, messages: &Catalog) -> String {
+    let mut out = description.to_string();
+    if let Some(choices) = argument.parser.choices() {
+        push(&mut out, messages.format("help.choices", &[("choices", &choices.join(", "))]));
+    }
+    if !default.is_empty() {
+        push(&mut out, messages.format("help.default", &[("default", &default)]));
+    }
+    out.trim_start().to_string()
+}
+
+/// One `  term   description` line, with the description wrapped and hanging under itself. The
+/// term is painted by `paint`, and padded by its unpainted width.
+fn row(term: &str, description: &str, column: usize, width: usize, paint: impl Fn(&str) -> String) -> String {
+    let indent = 2 + column + 2;
+    let lines = wrap(description, width.saturating_sub(indent).max(20));
+    if lines.is_empty() {
+        return format!("  {}\n", paint(term));
+    }
+    let mut out = format!("  {}{:pad$}", paint(term), "", pad = column.saturating_sub(term.chars().count()));
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 {
+            out.push_str(&format!("  {}\n", line));
+        } else {
+            out.push_str(&format!("{:indent$}{}\n", "", line, indent = indent));
+        }
+    }
+    out
+}
+
+/// Greedily wraps `text` into lines of at most `width` characters. Words longer than `width`
+/// get a line of their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::ProgramOption;
+    use crate::examples::oop::value::EnumParser;
+
+    #[test]
+    fn renders_every_section() {
+        let lists = Program::new(String::from("lists"));
+        let program = lists
+            .command(String::from("bench"))
+            .description(String::from("Times the linked lists."))
+            .argument(ProgramArgument::new(String::from("<size>"), String::from("number of elements")))
+            .option(ProgramOption::new(String::from("-r, --runs <count>"), String::from("how often to run")).default(String::from("1")))
+            .add_command(Program::new(String::from("push")).description(String::from("only push")));
+
+        assert_eq!(render(&program, &Config::default(), 80, Style::default()), "\
+Usage: lists bench [options] <size> [command]
+
+Times the linked lists.
+
+Arguments:
+  size                number of elements
+
+Options:
+  -r, --runs <count>  how often to run (default: 1)
+  -h, --help          display help for command
+
+Commands:
+  push                only push
+  help [command]      display help for command
+");
+    }
+
+    #[test]
+    fn wraps_descriptions_to_width() {
+        let program = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("-a"), String::from("one two three four five six seven eight nine ten")));
+        let help = render(&program, &Config::default(), 30, Style::default());
+        assert!(help.contains("\
+  -a          one two three four
+              five six seven eight
+              nine ten
+"));
+    }
+
+    #[test]
+    fn lists_choices() {
+        let program = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("--shell <name>"), String::from("target shell"))
+                .parser(EnumParser(vec![String::from("bash"), String::from("zsh")]))
+                .default(String::from("bash")));
+        let help = render(&program, &Config::default(), 80, Style::default());
+        assert!(help.contains("  --shell <name>  target shell (choices: bash, zsh) (default: bash)\n"));
+    }
+
+    #[test]
+    fn shows_where_values_come_from() {
+        let program = Program::new(String::from("root"))
+            .option(ProgramOption::new(String::from("--runs <n>"), String::from("runs")).config(String::from("bench.runs")))
+            .option(ProgramOption::new(String::from("--size <n>"), String::from("size")).env(String::from("HOW_TO_RUST_TEST_NO_SUCH_VAR")));
+        let config = Config::parse("[bench]\nruns = 4\n").unwrap();
+        let help = render(&program, &config, 100, Style::default());
+        assert!(help.contains("  --runs <n>  runs (config: bench.runs) (current: 4 from config bench.runs)\n"));
+        assert!(help.contains("  --size <n>  size (env: HOW_TO_RUST_TEST_NO_SUCH_VAR)\n"));
+    }
+
+    #[test]
+    fn lists_aliases_and_skips_hidden_commands() {
+        let program = Program::new(String::from("root"));
+        program.command(String::from("list")).alias(String::from("ls")).description(String::from("list things"));
+        program.command(String::from("secret")).hidden();
+        let help = render(&program, &Config::default(), 80, Style::default());
+        assert!(help.contains("  list|ls         list things\n"));
+        assert!(!help.contains("secret"));
+    }
+
+    #[test]
+    fn lists_groups_and_relations() {
+        use crate::examples::oop::GroupRule;
+
+        let program = Program::new(String::from("root"))
+            .group(String::from("format"), GroupRule::ExactlyOne)
+            .option(ProgramOption::new(String::from("-q"), String::from("quiet")).conflicts_with(String::from("verbose")))
+            .option(ProgramOption::new(String::from("--json"), String::from("as JSON")).group(String::from("format")))
+            .option(ProgramOption::new(String::from("--yaml"), String::from("as YAML")).group(String::from("format")).requires(String::from("q")));
+        let help = render(&program, &Config::default(), 80, Style::default());
+        assert!(help.contains("\
+Options:
+  -q          quiet (conflicts with --verbose)
+  -h, --help  display help for command
+
+Format (exactly one):
+  --json      as JSON
+  --yaml      as YAML (requires -q)
+"));
+    }
+
+    #[test]
+    fn styles_headings_and_command_names() {
+        let program = Program::new(String::from("root")).add_command(Program::new(String::from("push")).description(String::from("push")));
+        let help = render(&program, &Config::default(), 80, Style::new(true));
+        assert!(help.contains("\n\x1b[1mOptions:\x1b[0m\n  -h, --help      display help for command\n"));
+        assert!(help.contains("\n\x1b[1mCommands:\x1b[0m\n  \x1b[36mpush\x1b[0m            push\n"));
+        assert!(help.contains("  \x1b[36mhelp [command]\x1b[0m  display help for command\n"));
+    }
+
+    #[test]
+    fn wraps_long_words_onto_their_own_line() {
+        assert_eq!(wrap("a verylongword b", 5), vec!["a", "verylongword", "b"]);
+    }
+
+    #[test]
+    fn measures_text_in_characters() {
+        assert_eq!(wrap("été été", 7), vec!["été été"]);
+        let program = Program::new(String::from("root"))
+            .argument(ProgramArgument::new(String::from("<crème_brûlée>"), String::from("dessert")));
+        let help = render(&program, &Config::default(), 80, Style::default());
+        assert!(help.contains("  crème_brûlée  dessert\n"));
+        assert!(help.contains("  -h, --help    display help for command\n"));
+    }
+}
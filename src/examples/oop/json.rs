@@ -0,0 +1,374 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A JSON value. Objects keep their keys in the order they were written or read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+/// Text that is not JSON, and where the reader gave up on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonError {
+    pub line: usize,
+    pub column: usize,
+    pub reason: String
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.reason)
+    }
+}
+
+impl Json {
+    /// Reads a JSON document. Nothing but whitespace may follow the value.
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut reader = Reader { chars: text.chars().peekable(), line: 1, column: 1, depth: 0 };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        match reader.chars.peek().copied() {
+            None => Ok(value),
+            Some(c) => Err(reader.error(format!("unexpected '{}' after the value", c)))
+        }
+    }
+
+    /// The value of `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None
+        }
+    }
+
+    /// A string, or `null` if there is none.
+    pub fn string_or_null(text: Option<&str>) -> Json {
+        text.map_or(Json::Null, |text| Json::String(String::from(text)))
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = |f: &mut fmt::Formatter<'_>, depth: usize| write!(f, "{:width$}", "", width = depth * 2);
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(text) => write_string(f, text),
+            Json::Array(items) if items.is_empty() => write!(f, "[]"),
+            Json::Object(entries) if entries.is_empty() => write!(f, "{{}}"),
+            Json::Array(items) => {
+                writeln!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    pad(f, indent + 1)?;
+                    item.write(f, indent + 1)?;
+                    writeln!(f, "{}", if index + 1 < items.len() { "," } else { "" })?;
+                }
+                pad(f, indent)?;
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                writeln!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    pad(f, indent + 1)?;
+                    write_string(f, key)?;
+                    write!(f, ": ")?;
+                    value.write(f, indent + 1)?;
+                    writeln!(f, "{}", if index + 1 < entries.len() { "," } else { "" })?;
+                }
+                pad(f, indent)?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Writes the value indented by two spaces per level. Numbers that are not finite, which JSON
+/// cannot hold, are written as `null`.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    write!(f, "\"")
+}
+
+/// How many arrays and objects can be open at once. Each one read is a call deeper, so without a
+/// limit a long enough run of `[` would overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    depth: usize
+}
+
+impl Reader<'_> {
+    fn error(&self, reason: String) -> JsonError {
+        JsonError { line: self.line, column: self.column, reason }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+            self.next();
+        }
+    }
+
+    /// Consumes `c` after any whitespace, or says what was found instead.
+    fn expect(&mut self, c: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some(found) if found == c => {
+                self.next();
+                Ok(())
+            }
+            Some(found) => Err(self.error(format!("expected '{}', found '{}'", c, found))),
+            None => Err(self.error(format!("expected '{}', found the end", c)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('{') => self.nested(Self::object),
+            Some('[') => self.nested(Self::array),
+            Some('"') => self.string().map(Json::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.literal(),
+            Some(c) => Err(self.error(format!("unexpected '{}'", c))),
+            None => Err(self.error(String::from("expected a value, found the end")))
+        }
+    }
+
+    /// Reads an array or object with `read`, one level deeper than the reader is.
+    fn nested(&mut self, read: fn(&mut Self) -> Result<Json, JsonError>) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("nested deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let value = read(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            self.column += 1;
+            return Ok(Json::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return Err(self.error(String::from("expected a string key")));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(entries)),
+                _ => return Err(self.error(String::from("expected ',' or '}' in an object")))
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            self.column += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err(self.error(String::from("expected ',' or ']' in an array")))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.next();
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error(String::from("invalid escape")))
+                    };
+                    text.push(escaped);
+                }
+                Some(c) if (c as u32) < 0x20 => return Err(self.error(String::from("control character in a string"))),
+                Some(c) => text.push(c),
+                None => return Err(self.error(String::from("unterminated string")))
+            }
+        }
+    }
+
+    /// The character of a `\uXXXX` escape whose `\u` was read, joining a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(self.error(String::from("unpaired surrogate")));
+            }
+            let low = self.hex()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error(String::from("unpaired surrogate")));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error(String::from("invalid unicode escape")))
+    }
+
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16)).ok_or_else(|| self.error(String::from("invalid unicode escape")))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let mut text = String::new();
+        while let Some(c) = self.chars.next_if(|c| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9')) {
+            self.column += 1;
+            text.push(c);
+        }
+        text.parse().map(Json::Number).map_err(|_| self.error(format!("invalid number '{}'", text)))
+    }
+
+    fn literal(&mut self) -> Result<Json, JsonError> {
+        let mut word = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+            self.column += 1;
+            word.push(c);
+        }
+        match word.as_str() {
+            "null" => Ok(Json::Null),
+            "true" => Ok(Json::Bool(true)),
+            "false" => Ok(Json::Bool(false)),
+            _ => Err(self.error(format!("unexpected '{}'", word)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_kind_of_value() {
+        let json = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\né😀"}, "d": []} "#).unwrap();
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                (String::from("a"), Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null])),
+                (String::from("b"), Json::Object(vec![(String::from("c"), Json::String(String::from("x\"\né😀")))])),
+                (String::from("d"), Json::Array(Vec::new()))
+            ])
+        );
+        assert_eq!(json.get("b").and_then(|b| b.get("c")).and_then(Json::as_str), Some("x\"\né😀"));
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let text = "{\n  \"name\": \"tab\\there\",\n  \"list\": [\n    1,\n    2.5\n  ],\n  \"empty\": {}\n}";
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.to_string(), text);
+        assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+    }
+
+    #[test]
+    fn points_at_errors() {
+        let error = |text: &str| Json::parse(text).unwrap_err();
+        assert_eq!(error("{\n  \"a\": tru\n}"), JsonError { line: 2, column: 11, reason: String::from("unexpected 'tru'") });
+        assert_eq!(error("[1, 2"), JsonError { line: 1, column: 6, reason: String::from("expected ',' or ']' in an array") });
+        assert_eq!(error("{} x").reason, "unexpected 'x' after the value");
+        assert_eq!(error("\"abc").reason, "unterminated string");
+    }
+
+    #[test]
+    fn reads_long_documents_and_rejects_deep_ones() {
+        let spaced = format!("{}[1]{}", "\n".repeat(200_000), "\n".repeat(200_000));
+        assert_eq!(Json::parse(&spaced), Ok(Json::Array(vec![Json::Number(1.0)])));
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        let error = Json::parse(&"[".repeat(1_000_000)).unwrap_err();
+        assert_eq!(error, JsonError { line: 1, column: MAX_DEPTH + 1, reason: String::from("nested deeper than 128 levels") });
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+    }
+}
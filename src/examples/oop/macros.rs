@@ -0,0 +1,248 @@
+/// Builds a `Program` tree from a declaration of its commands. Each command is a name and a block
+/// of items, one of:
+///
+/// ```ignore
+/// program! {
+///     "tool" {
+///         version "1.0.0";
+///         description "Does tool things";
+///         alias "t";
+///         hidden;
+///         external_subcommands;
+///         response_files;
+///         color ColorChoice::Auto;
+///         color_flag;
+///         config_file "tool.toml";
+///         output BasicLogger;
+///         group "format" ExactlyOne;
+///         option "-o, --output <file>" "where to write" .default("out.txt".into());
+///         argument "<size>" "how many" .parser(IntegerParser);
+///         pre_action |context| Ok(());
+///         action |context| Ok(());
+///         post_action |context| Ok(());
+///         command "remote" {
+///             description "Manage remotes";
+///         }
+///     }
+/// }
+/// ```
+///
+/// Options and arguments take the same builder calls as `ProgramOption` and `ProgramArgument`
+/// after their description. Two commands with the same name in one block, or arguments in an
+/// order `Program::argument` would panic on, fail to compile.
+#[macro_export]
+macro_rules! program {
+    ($name:literal { $($body:tt)* }) => {{
+        let program = $crate::examples::oop::Program::new(::std::string::String::from($name));
+        $crate::program!(@items program [] []; $($body)*);
+        program
+    }};
+
+    // Every item has been added: check the names that were collected on the way.
+    (@items $program:ident [$($commands:literal)*] [$($arguments:literal)*];) => {
+        const _: () = assert!(
+            !$crate::examples::oop::macros::has_duplicates(&[$($commands),*]),
+            "program! declares two commands with the same name"
+        );
+        const _: () = assert!(
+            $crate::examples::oop::macros::arguments_in_order(&[$($arguments),*]),
+            "program! declares a required argument after an optional one, or an argument after a variadic one"
+        );
+    };
+    (@items $program:ident $commands:tt $arguments:tt; version $version:expr; $($rest:tt)*) => {
+        let $program = $program.version(::std::string::String::from($version));
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; description $description:expr; $($rest:tt)*) => {
+        let $program = $program.description(::std::string::String::from($description));
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; alias $alias:expr; $($rest:tt)*) => {
+        let $program = $program.alias(::std::string::String::from($alias));
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; hidden; $($rest:tt)*) => {
+        let $program = $program.hidden();
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; external_subcommands; $($rest:tt)*) => {
+        let $program = $program.external_subcommands();
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; response_files; $($rest:tt)*) => {
+        let $program = $program.response_files();
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; color $choice:expr; $($rest:tt)*) => {
+        let $program = $program.color($choice);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; color_flag; $($rest:tt)*) => {
+        let $program = $program.color_flag();
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; config_file $path:expr; $($rest:tt)*) => {
+        let $program = $program.config_file(::std::path::PathBuf::from($path));
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; output $logger:expr; $($rest:tt)*) => {
+        let $program = $program.output($logger);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; group $name:literal $rule:ident; $($rest:tt)*) => {
+        let $program = $program.group(::std::string::String::from($name), $crate::examples::oop::GroupRule::$rule);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt;
+        option $flags:literal $description:literal $(.$method:ident($($value:tt)*))*; $($rest:tt)*
+    ) => {
+        let $program = $program.option(
+            $crate::examples::oop::ProgramOption::new(
+                ::std::string::String::from($flags),
+                ::std::string::String::from($description)
+            )
+            $(.$method($($value)*))*
+        );
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt [$($arguments:literal)*];
+        argument $name:literal $description:literal $(.$method:ident($($value:tt)*))*; $($rest:tt)*
+    ) => {
+        let $program = $program.argument(
+            $crate::examples::oop::ProgramArgument::new(
+                ::std::string::String::from($name),
+                ::std::string::String::from($description)
+            )
+            $(.$method($($value)*))*
+        );
+        $crate::program!(@items $program $commands [$($arguments)* $name]; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; pre_action $hook:expr; $($rest:tt)*) => {
+        let $program = $program.pre_action($hook);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; post_action $hook:expr; $($rest:tt)*) => {
+        let $program = $program.post_action($hook);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident $commands:tt $arguments:tt; action $action:expr; $($rest:tt)*) => {
+        let $program = $program.action($action);
+        $crate::program!(@items $program $commands $arguments; $($rest)*);
+    };
+    (@items $program:ident [$($commands:literal)*] $arguments:tt;
+        command $name:literal { $($body:tt)* } $($rest:tt)*
+    ) => {
+        let $program = $program.add_command($crate::program!($name { $($body)* }));
+        $crate::program!(@items $program [$($commands)* $name] $arguments; $($rest)*);
+    };
+}
+
+/// Whether any two of `names` are equal. Used by `program!` at compile time.
+#[doc(hidden)]
+pub const fn has_duplicates(names: &[&str]) -> bool {
+    let mut i = 0;
+    while i < names.len() {
+        let mut j = i + 1;
+        while j < names.len() {
+            if str_eq(names[i], names[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Whether `Program::argument` accepts arguments declared in this order: nothing after a
+/// variadic argument, and no required argument after an optional one. Used by `program!` at
+/// compile time.
+#[doc(hidden)]
+pub const fn arguments_in_order(names: &[&str]) -> bool {
+    let mut i = 1;
+    while i < names.len() {
+        let previous = names[i - 1].as_bytes();
+        let current = names[i].as_bytes();
+        if is_variadic(previous) || (is_optional(previous) && !is_optional(current)) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn is_optional(name: &[u8]) -> bool {
+    !name.is_empty() && name[0] == b'['
+}
+
+/// Whether the name ends in `...>` or `...]`.
+const fn is_variadic(name: &[u8]) -> bool {
+    let n = name.len();
+    n >= 4 && name[n - 4] == b'.' && name[n - 3] == b'.' && name[n - 2] == b'.'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::value::IntegerParser;
+
+    #[test]
+    fn finds_duplicate_names() {
+        assert!(!has_duplicates(&[]));
+        assert!(!has_duplicates(&["list", "lists"]));
+        assert!(has_duplicates(&["list", "bench", "list"]));
+    }
+
+    #[test]
+    fn checks_argument_order() {
+        assert!(arguments_in_order(&["<a>", "[b]", "[c...]"]));
+        assert!(!arguments_in_order(&["[a]", "<b>"]));
+        assert!(!arguments_in_order(&["<a...>", "[b]"]));
+    }
+
+    #[test]
+    fn builds_the_declared_tree() {
+        let program = crate::program! {
+            "tool" {
+                version "1.0.0";
+                description "Does tool things";
+                option "-r, --runs <count>" "how often" .parser(IntegerParser).default("1".into());
+                command "lists" {
+                    alias "ls";
+                    argument "<size>" "how many" .parser(IntegerParser);
+                    argument "[names...]" "which";
+                    action |context| {
+                        assert_eq!(context.matches().get::<i64>("size"), Some(&3));
+                        assert_eq!(context.globals().get::<i64>("runs"), Some(&2));
+                        Ok(())
+                    };
+                }
+                command "secret" {
+                    hidden;
+                }
+            }
+        };
+
+        assert_eq!(program.name(), "tool");
+        let matches = program.dispatch(&[String::from("ls"), String::from("3"), String::from("-r"), String::from("2")]).unwrap();
+        assert_eq!(matches.get_many::<String>("names").len(), 0);
+        assert!(program.help().contains("lists|ls"));
+        assert!(!program.help().contains("secret"));
+        assert!(program.borrow().arguments.is_none());
+    }
+}
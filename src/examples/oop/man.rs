@@ -0,0 +1,221 @@
+use std::fmt::Write;
+
+use super::help::{annotate, describe_color, describe_option, COLOR_FLAGS, HELP_FLAGS, VERSION_FLAGS};
+use super::Program;
+
+/// Renders a page for `program` and every visible command below it, named after the command path
+/// joined with dashes.
+pub fn render(program: &Program) -> Vec<(String, String)> {
+    let version = root(program).borrow().version.clone();
+    let mut pages = Vec::new();
+    collect(program, version.as_deref(), &mut pages);
+    pages
+}
+
+fn root(program: &Program) -> Program {
+    match program.parent() {
+        Some(parent) => root(&parent),
+        None => program.clone()
+    }
+}
+
+fn collect(program: &Program, version: Option<&str>, pages: &mut Vec<(String, String)>) {
+    pages.push((format!("{}.1", page_name(program)), page(program, version)));
+    for child in program.borrow().visible_children() {
+        collect(child, version, pages);
+    }
+}
+
+/// The name a command's page is found under, e.g. `git-remote` for `git remote`.
+fn page_name(program: &Program) -> String {
+    program.path().join("-")
+}
+
+fn page(program: &Program, version: Option<&str>) -> String {
+    let name = page_name(program);
+    let path = program.path();
+    let color_flag = program.accepts_color_flag();
+    let messages = program.catalog();
+    let program = program.borrow();
+    let mut out = String::new();
+
+    let source = match version {
+        Some(version) => format!("{} {}", path[0], version),
+        None => path[0].clone()
+    };
+    let _ = writeln!(out, ".TH {} 1 \"\" {} \"User Commands\"", quote(&name.to_uppercase()), quote(&source));
+
+    out.push_str(".SH NAME\n");
+    match &program.description {
+        Some(description) => { let _ = writeln!(out, "{} \\- {}", escape(&name), escape(description)); }
+        None => out.push_str(&format!("{}\n", escape(&name)))
+    }
+
+    out.push_str(".SH SYNOPSIS\n");
+    let _ = writeln!(out, ".B {}", escape(&path.join(" ")));
+    let mut synopsis = vec![String::from("[\\fIoptions\\fR]")];
+    for argument in program.arguments.iter().flatten() {
+        synopsis.push(format!("\\fI{}\\fR", escape(&argument.name)));
+    }
+    if !program.children.is_empty() {
+        synopsis.push(String::from("[\\fIcommand\\fR]"));
+    }
+    let _ = writeln!(out, "{}", synopsis.join(" "));
+
+    if let Some(description) = &program.description {
+        out.push_str(".SH DESCRIPTION\n");
+        let _ = writeln!(out, "{}", escape(description));
+    }
+
+    if let Some(arguments) = program.arguments.as_ref().filter(|arguments| !arguments.is_empty()) {
+        out.push_str(".SH ARGUMENTS\n");
+        for argument in arguments {
+            out.push_str(&item(&format!("\\fI{}\\fR", escape(&argument.key())), &annotate(argument, &argument.description, &argument.default, &messages)));
+        }
+    }
+
+    out.push_str(".SH OPTIONS\n");
+    for option in program.options.iter().flatten() {
+        out.push_str(&item(&flags(&option.flags), &describe_option(option, &messages)));
+    }
+    if program.version.is_some() {
+        out.push_str(&item(&flags(VERSION_FLAGS), messages.get("help.version")));
+    }
+    if color_flag {
+        out.push_str(&item(&flags(COLOR_FLAGS), &describe_color(&messages)));
+    }
+    out.push_str(&item(&flags(HELP_FLAGS), messages.get("help.help")));
+
+    let children = program.visible_children();
+    if !children.is_empty() {
+        out.push_str(".SH COMMANDS\n");
+        for child in children {
+            let see = format!("See \\fB{}\\fR(1).", escape(&page_name(child)));
+            let child = child.borrow();
+            let names: Vec<String> = std::iter::once(&child.name)
+                .chain(&child.aliases)
+                .map(|name| format!("\\fB{}\\fR", escape(name)))
+                .collect();
+            let description = match &child.description {
+                Some(description) => format!("{}\n{}", escape(description), see),
+                None => see
+            };
+            let _ = write!(out, ".TP\n{}\n{}\n", names.join(", "), description);
+        }
+    }
+    out
+}
+
+/// A `.TP` paragraph: the tag on the first line, the indented description below it.
+fn item(tag: &str, description: &str) -> String {
+    if description.is_empty() {
+        return format!(".TP\n{}\n", tag);
+    }
+    format!(".TP\n{}\n{}\n", tag, escape(description))
+}
+
+/// Option flags such as `-o, --output <file>`, with the flags in bold and placeholders in italics.
+fn flags(flags: &str) -> String {
+    flags
+        .split_whitespace()
+        .map(|word| {
+            let (word, separator) = match word.strip_suffix(',') {
+                Some(word) => (word, ","),
+                None => (word, "")
+            };
+            let font = if word.starts_with('<') || word.starts_with('[') { 'I' } else { 'B' };
+            format!("\\f{}{}\\fR{}", font, escape(word), separator)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Escapes text so roff prints it as written: backslashes and dashes are escaped, and a line
+/// starting with a control character is guarded with a zero-width `\&`.
+fn escape(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\\(dq"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::{ProgramArgument, ProgramOption};
+
+    fn program() -> Program {
+        Program::new(String::from("tool"))
+            .version(String::from("1.2.3"))
+            .description(String::from("Does tool things."))
+            .option(ProgramOption::new(String::from("-o, --output <file>"), String::from("where to write")))
+            .add_command(
+                Program::new(String::from("remote"))
+                    .alias(String::from("rm"))
+                    .description(String::from("Manage remotes"))
+                    .argument(ProgramArgument::new(String::from("<name>"), String::from("remote name")))
+            )
+            .add_command(Program::new(String::from("secret")).hidden())
+    }
+
+    #[test]
+    fn renders_every_section() {
+        let pages = program().man_pages();
+        assert_eq!(pages[0].0, "tool.1");
+        assert_eq!(pages[0].1, "\
+.TH \"TOOL\" 1 \"\" \"tool 1.2.3\" \"User Commands\"
+.SH NAME
+tool \\- Does tool things.
+.SH SYNOPSIS
+.B tool
+[\\fIoptions\\fR] [\\fIcommand\\fR]
+.SH DESCRIPTION
+Does tool things.
+.SH OPTIONS
+.TP
+\\fB\\-o\\fR, \\fB\\-\\-output\\fR \\fI<file>\\fR
+where to write
+.TP
+\\fB\\-V\\fR, \\fB\\-\\-version\\fR
+output the version number
+.TP
+\\fB\\-h\\fR, \\fB\\-\\-help\\fR
+display help for command
+.SH COMMANDS
+.TP
+\\fBremote\\fR, \\fBrm\\fR
+Manage remotes
+See \\fBtool\\-remote\\fR(1).
+");
+    }
+
+    #[test]
+    fn writes_a_page_per_visible_subcommand() {
+        let root = program();
+        let pages = root.man_pages();
+        let names: Vec<&str> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["tool.1", "tool-remote.1"]);
+
+        let remote = &pages[1].1;
+        assert!(remote.starts_with(".TH \"TOOL-REMOTE\" 1 \"\" \"tool 1.2.3\" \"User Commands\"\n"));
+        assert!(remote.contains(".B tool remote\n[\\fIoptions\\fR] \\fI<name>\\fR\n"));
+        assert!(remote.contains(".SH ARGUMENTS\n.TP\n\\fIname\\fR\nremote name\n"));
+        assert!(!remote.contains("\\-\\-version"));
+    }
+
+    #[test]
+    fn escapes_roff_control_characters() {
+        assert_eq!(escape(".hidden\n'quoted\na\\b-c"), "\\&.hidden\n\\&'quoted\na\\eb\\-c");
+    }
+}
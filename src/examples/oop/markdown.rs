@@ -0,0 +1,160 @@
+use std::fmt::Write;
+
+use super::help::{annotate, describe_color, describe_option, usage, COLOR_FLAGS, HELP_FLAGS, VERSION_FLAGS};
+use super::Program;
+
+/// Renders a reference for `program` and every visible command below it: one section per command,
+/// each with an anchor named after the command path joined with dashes, so `git remote` can be
+/// linked to as `#git-remote`.
+pub fn render(program: &Program) -> String {
+    let mut out = String::new();
+    collect(program, &mut out);
+    out
+}
+
+fn collect(program: &Program, out: &mut String) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&section(program));
+    for child in program.borrow().visible_children() {
+        collect(child, out);
+    }
+}
+
+fn anchor(program: &Program) -> String {
+    program.path().join("-")
+}
+
+fn section(program: &Program) -> String {
+    let path = program.path();
+    let mut out = String::new();
+    let _ = writeln!(out, "<a id=\"{}\"></a>", anchor(program));
+    let _ = writeln!(out, "### `{}`\n", path.join(" "));
+
+    let color_flag = program.accepts_color_flag();
+    let messages = program.catalog();
+    let program = program.borrow();
+    if let Some(description) = &program.description {
+        let _ = writeln!(out, "{}\n", description);
+    }
+    let _ = writeln!(out, "```text\n{}\n```", usage(&program, &path));
+
+    let arguments: Vec<(String, String)> = program
+        .arguments
+        .iter()
+        .flatten()
+        .map(|argument| (format!("`{}`", argument.key()), annotate(argument, &argument.description, &argument.default, &messages)))
+        .collect();
+    out.push_str(&table("Argument", &arguments));
+
+    let mut options: Vec<(String, String)> = program
+        .options
+        .iter()
+        .flatten()
+        .map(|option| (format!("`{}`", option.flags), describe_option(option, &messages)))
+        .collect();
+    if program.version.is_some() {
+        options.push((format!("`{}`", VERSION_FLAGS), String::from(messages.get("help.version"))));
+    }
+    if color_flag {
+        options.push((format!("`{}`", COLOR_FLAGS), describe_color(&messages)));
+    }
+    options.push((format!("`{}`", HELP_FLAGS), String::from(messages.get("help.help"))));
+    out.push_str(&table("Option", &options));
+
+    let commands: Vec<(String, String)> = program
+        .visible_children()
+        .into_iter()
+        .map(|child| {
+            let link = format!("[`{}`](#{})", child.name(), anchor(child));
+            let child = child.borrow();
+            let term = std::iter::once(link)
+                .chain(child.aliases.iter().map(|alias| format!("`{}`", alias)))
+                .collect::<Vec<String>>()
+                .join(", ");
+            (term, child.description.clone().unwrap_or_default())
+        })
+        .collect();
+    out.push_str(&table("Command", &commands));
+    out
+}
+
+/// A two column table headed `heading | Description`, or nothing if there are no rows.
+fn table(heading: &str, rows: &[(String, String)]) -> String {
+    if rows.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("\n| {} | Description |\n| --- | --- |\n", heading);
+    for (term, description) in rows {
+        let _ = writeln!(out, "| {} | {} |", escape(term), escape(description));
+    }
+    out
+}
+
+/// Keeps a cell on one line and inside its column.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::{ProgramArgument, ProgramOption};
+
+    #[test]
+    fn renders_a_section_per_visible_command() {
+        let program = Program::new(String::from("tool"))
+            .description(String::from("Does tool things."))
+            .option(ProgramOption::new(String::from("-o, --output <file>"), String::from("where to write")))
+            .add_command(
+                Program::new(String::from("remote"))
+                    .alias(String::from("rm"))
+                    .description(String::from("Manage remotes"))
+                    .argument(ProgramArgument::new(String::from("<name>"), String::from("remote name")))
+            )
+            .add_command(Program::new(String::from("secret")).hidden());
+
+        assert_eq!(render(&program), "\
+<a id=\"tool\"></a>
+### `tool`
+
+Does tool things.
+
+```text
+tool [options] [command]
+```
+
+| Option | Description |
+| --- | --- |
+| `-o, --output <file>` | where to write |
+| `-h, --help` | display help for command |
+
+| Command | Description |
+| --- | --- |
+| [`remote`](#tool-remote), `rm` | Manage remotes |
+
+<a id=\"tool-remote\"></a>
+### `tool remote`
+
+Manage remotes
+
+```text
+tool remote [options] <name>
+```
+
+| Argument | Description |
+| --- | --- |
+| `name` | remote name |
+
+| Option | Description |
+| --- | --- |
+| `-h, --help` | display help for command |
+");
+    }
+
+    #[test]
+    fn escapes_table_cells() {
+        assert_eq!(escape("a|b\nc"), "a\\|b c");
+    }
+}
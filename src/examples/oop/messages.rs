@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// The English text of every message, by id. `{name}` marks a placeholder.
+const ENGLISH: &[(&str, &str)] = &[
+    ("help.usage", "Usage: {usage}"),
+    ("help.arguments", "Arguments"),
+    ("help.options", "Options"),
+    ("help.commands", "Commands"),
+    ("help.group", "{group} ({rule})"),
+    ("help.help", "display help for command"),
+    ("help.version", "output the version number"),
+    ("help.color", "when to color help and errors"),
+    ("help.choices", "(choices: {choices})"),
+    ("help.default", "(default: {default})"),
+    ("help.repeatable", "(repeatable)"),
+    ("help.delimiter", "(separated by '{delimiter}')"),
+    ("help.env", "(env: {name})"),
+    ("help.config", "(config: {key})"),
+    ("help.conflicts", "(conflicts with {flag})"),
+    ("help.requires", "(requires {flag})"),
+    ("help.current", "(current: {value} from {source})"),
+    ("rule.exactly_one", "exactly one"),
+    ("rule.at_least_one", "at least one"),
+    ("source.command_line", "command line"),
+    ("source.env", "env {name}"),
+    ("source.config", "config {key}"),
+    ("source.default", "default"),
+    ("error.prefix", "error: {message}"),
+    ("error.unknown_command", "Unknown command '{name}'"),
+    ("error.unknown_option", "Unknown option '{name}'"),
+    ("error.suggestion", " (Did you mean '{suggestion}'?)"),
+    ("error.missing_argument", "Missing required argument '{name}'"),
+    ("error.unexpected_argument", "Unexpected argument '{value}'"),
+    ("error.invalid_value", "Invalid value '{value}' for {kind} '{name}': {reason}"),
+    ("error.missing_value", "Option '{option}' expects a value"),
+    ("error.unexpected_value", "Option '{option}' does not take a value"),
+    ("error.missing_command", "Missing command for '{command}'"),
+    ("error.not_implemented", "Command not implemented: '{command}'"),
+    ("error.invalid_config", "Invalid config file {path}:{line}: {reason}"),
+    ("error.unreadable_config", "Cannot read config file {path}: {reason}"),
+    ("error.invalid_response_file", "Invalid response file {path}:{line}: {reason}"),
+    ("error.unreadable_response_file", "Cannot read response file {path}: {reason}"),
+    ("error.invalid_encoding", "Argument '{value}' is not valid UTF-8"),
+    ("error.conflicting_options", "Option '{option}' cannot be used with '{other}'"),
+    ("error.missing_required_option", "Option '{option}' requires '{required}'"),
+    ("error.missing_group", "One of '{options}' is required (group '{group}')"),
+    ("error.undeclared_group", "Option '{option}' belongs to the undeclared group '{group}'"),
+    ("error.action_failed", "Command '{command}' failed: {message}"),
+    ("error.external_failed", "Command '{command}' exited with code {code}"),
+    ("kind.argument", "argument"),
+    ("kind.option", "option"),
+    ("value.expected", "expected {expected}"),
+    ("value.string", "a string"),
+    ("value.integer", "an integer"),
+    ("value.float", "a number"),
+    ("value.bool", "a boolean"),
+    ("value.path", "a path"),
+    ("value.enum", "one of {choices}"),
+    ("prompt.invalid", "Invalid value '{value}': {reason}"),
+    ("prompt.default_no", "[y/N]"),
+    ("prompt.default_yes", "[Y/n]"),
+    ("prompt.yes", "y"),
+    ("prompt.no", "n")
+];
+
+/// The messages `Program` shows in help, errors and prompts, in one locale. Messages the catalog
+/// does not have come from its fallback, then from the built-in English text, so a translation
+/// can be partial:
+///
+/// ```ignore
+/// let french = Catalog::new("fr")
+///     .message("help.options", "Options")
+///     .message("error.unknown_command", "Commande inconnue « {name} »");
+/// let program = Program::new(String::from("outil")).messages(Catalog::new("fr-CA").fallback(french));
+/// ```
+///
+/// The ids and their English text are listed in `ENGLISH` in this file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Catalog {
+    locale: String,
+    messages: HashMap<String, String>,
+    fallback: Option<Box<Catalog>>
+}
+
+impl Catalog {
+    /// An empty catalog for `locale`, such as `"fr"`, which shows English until messages are
+    /// added.
+    pub fn new(locale: &str) -> Self {
+        Self { locale: String::from(locale), ..Catalog::default() }
+    }
+
+    /// The built-in English messages.
+    pub fn english() -> Self {
+        Catalog::new("en")
+    }
+
+    /// Sets the text of the message `id`. `{placeholder}`s in it are filled in when it is shown.
+    #[allow(dead_code)]
+    pub fn message(mut self, id: &str, text: &str) -> Self {
+        self.messages.insert(String::from(id), String::from(text));
+        self
+    }
+
+    /// Sets the catalog to look in for messages this one does not have, e.g. `fr` for `fr-CA`.
+    #[allow(dead_code)]
+    pub fn fallback(mut self, catalog: Catalog) -> Self {
+        self.fallback = Some(Box::new(catalog));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// The text of `id`, from this catalog, its fallbacks or English. An unknown id is its own
+    /// text, so a typo shows up instead of an empty message.
+    pub fn get<'a>(&'a self, id: &'a str) -> &'a str {
+        let mut catalog = Some(self);
+        while let Some(current) = catalog {
+            if let Some(text) = current.messages.get(id) {
+                return text;
+            }
+            catalog = current.fallback.as_deref();
+        }
+        ENGLISH.iter().find(|(english, _)| *english == id).map_or(id, |(_, text)| text)
+    }
+
+    /// The text of `id` with its placeholders replaced by `values`. Placeholders without a value
+    /// are left as they are.
+    pub fn format(&self, id: &str, values: &[(&str, &dyn Display)]) -> String {
+        let text = self.get(id);
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            let value = after
+                .find('}')
+                .and_then(|close| values.iter().find(|(name, _)| *name == &after[..close]).map(|(_, value)| (close, value)));
+            match value {
+                Some((close, value)) => {
+                    out.push_str(&value.to_string());
+                    rest = &after[close + 1..];
+                }
+                None => {
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_placeholders() {
+        let english = Catalog::english();
+        assert_eq!(english.format("error.unknown_command", &[("name", &"opo")]), "Unknown command 'opo'");
+        assert_eq!(english.format("help.current", &[("value", &4), ("other", &"x")]), "(current: 4 from {source})");
+        let braces = Catalog::english().message("x", "{a} {{a}} {b");
+        assert_eq!(braces.format("x", &[("a", &"{b}")]), "{b} {{b}} {b");
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        let french = Catalog::new("fr").message("help.options", "Options").message("help.commands", "Commandes");
+        let canadian = Catalog::new("fr-CA").message("help.commands", "Commandes (CA)").fallback(french);
+        assert_eq!(canadian.get("help.commands"), "Commandes (CA)");
+        assert_eq!(canadian.get("help.options"), "Options");
+        assert_eq!(canadian.get("help.arguments"), "Arguments");
+        assert_eq!(canadian.get("no.such.id"), "no.such.id");
+        assert_eq!(canadian.locale(), "fr-CA");
+    }
+}
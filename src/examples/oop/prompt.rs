@@ -0,0 +1,181 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use super::messages::Catalog;
+use super::ProgramArgument;
+
+/// Asks for required arguments that were not given on the command line. `Program::parse` uses
+/// one on the terminal; tests can hand `Program::prompter` one over in-memory buffers.
+pub struct Prompter {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    /// Whether someone is there to answer. Nothing is asked otherwise.
+    interactive: bool,
+    /// Whether the input is the terminal, whose echo has to be turned off for passwords.
+    terminal: bool
+}
+
+impl Prompter {
+    /// A prompter that asks on `output` and reads the answers from `input`.
+    #[allow(dead_code)]
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
+        Self { input: Box::new(input), output: Box::new(output), interactive: true, terminal: false }
+    }
+
+    /// A prompter that reads stdin and asks on stderr, so it does not mix with the output. It only
+    /// asks when stdin is a terminal.
+    pub fn terminal() -> Self {
+        let interactive = std::io::stdin().is_terminal();
+        Self {
+            input: Box::new(std::io::BufReader::new(std::io::stdin())),
+            output: Box::new(std::io::stderr()),
+            interactive,
+            terminal: interactive
+        }
+    }
+
+    /// Asks on `output` instead.
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Asks for `argument` until the answer parses, and returns it. Returns `None` when there is
+    /// nobody to ask or the input ends.
+    ///
+    /// An empty answer takes the default, if there is one. Arguments with choices list them,
+    /// `confirm` arguments ask a yes/no question and `password` arguments are read without echo.
+    /// The question is in the language of `messages`, and so are the yes and no answers, though
+    /// `y` and `n` always work.
+    pub fn ask(&mut self, argument: &ProgramArgument, messages: &Catalog) -> Option<String> {
+        if !self.interactive {
+            return None;
+        }
+        let question = question(argument, messages);
+        let (yes, no) = (messages.get("prompt.yes").to_lowercase(), messages.get("prompt.no").to_lowercase());
+        loop {
+            let _ = write!(self.output, "{}: ", question);
+            let _ = self.output.flush();
+
+            let answer = if argument.password { self.read_hidden()? } else { self.read_line()? };
+            let answer = match answer.to_lowercase() {
+                lower if lower.is_empty() && !argument.default.is_empty() => argument.default.clone(),
+                lower if lower.is_empty() && argument.confirm => String::from("false"),
+                lower if lower.is_empty() => continue,
+                lower if argument.confirm && (lower == "y" || lower == yes) => String::from("true"),
+                lower if argument.confirm && (lower == "n" || lower == no) => String::from("false"),
+                _ => answer
+            };
+            // Each of the space separated values given for a variadic argument has to parse.
+            let checked = if argument.is_variadic() {
+                answer.split_whitespace().try_for_each(|value| argument.check(value, messages).map(drop))
+            } else {
+                argument.check(&answer, messages).map(drop)
+            };
+            match checked {
+                Ok(()) => return Some(answer),
+                Err(reason) => {
+                    let _ = writeln!(self.output, "{}", messages.format("prompt.invalid", &[("value", &answer), ("reason", &reason)]));
+                }
+            }
+        }
+    }
+
+    /// The next line without its line ending, or `None` at the end of the input.
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(String::from(line.trim_end_matches(['\r', '\n'])))
+        }
+    }
+
+    fn read_hidden(&mut self) -> Option<String> {
+        if self.terminal {
+            set_echo(false);
+        }
+        let line = self.read_line();
+        if self.terminal {
+            set_echo(true);
+        }
+        // The newline typed after the password was not echoed.
+        let _ = writeln!(self.output);
+        line
+    }
+}
+
+/// The question for an argument: its description or name, its choices and its default.
+fn question(argument: &ProgramArgument, messages: &Catalog) -> String {
+    let mut question = if argument.description.is_empty() { argument.key() } else { argument.description.clone() };
+    if let Some(choices) = argument.parser.choices() {
+        question.push_str(&format!(" ({})", choices.join(", ")));
+    }
+    if argument.confirm {
+        let yes = argument.parser.parse(&argument.default).and_then(|value| value.downcast_ref::<bool>().copied());
+        question.push(' ');
+        question.push_str(messages.get(if yes == Some(true) { "prompt.default_yes" } else { "prompt.default_no" }));
+    } else if !argument.default.is_empty() && !argument.password {
+        question.push_str(&format!(" [{}]", argument.default));
+    }
+    question
+}
+
+#[cfg(unix)]
+fn set_echo(on: bool) {
+    let _ = std::process::Command::new("stty").arg(if on { "echo" } else { "-echo" }).status();
+}
+
+#[cfg(not(unix))]
+fn set_echo(_on: bool) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::tests::Buffer;
+    use crate::examples::oop::value::{EnumParser, IntegerParser};
+
+    fn ask(argument: ProgramArgument, input: &'static str) -> (Option<String>, String) {
+        let output = Buffer::default();
+        let answer = Prompter::new(input.as_bytes(), output.clone()).ask(&argument, &Catalog::english());
+        (answer, output.text())
+    }
+
+    #[test]
+    fn asks_again_until_the_answer_parses() {
+        let argument = ProgramArgument::new(String::from("<size>"), String::from("list size")).parser(IntegerParser);
+        let (answer, written) = ask(argument, "\nten\n10\n");
+        assert_eq!(answer.as_deref(), Some("10"));
+        assert_eq!(written, "list size: list size: Invalid value 'ten': expected an integer\nlist size: ");
+    }
+
+    #[test]
+    fn offers_the_default_and_choices() {
+        let argument = ProgramArgument::new(String::from("<shell>"), String::new())
+            .parser(EnumParser(vec![String::from("bash"), String::from("zsh")]))
+            .default(String::from("zsh"));
+        let (answer, written) = ask(argument, "\n");
+        assert_eq!(answer.as_deref(), Some("zsh"));
+        assert_eq!(written, "shell (bash, zsh) [zsh]: ");
+    }
+
+    #[test]
+    fn confirms_with_yes_or_no() {
+        let argument = || ProgramArgument::new(String::from("<force>"), String::from("Overwrite?")).confirm();
+        assert_eq!(ask(argument(), "y\n"), (Some(String::from("true")), String::from("Overwrite? [y/N]: ")));
+        assert_eq!(ask(argument(), "\n").0.as_deref(), Some("false"));
+        assert_eq!(ask(argument().default(String::from("yes")), "\n").0.as_deref(), Some("yes"));
+    }
+
+    #[test]
+    fn reads_passwords_without_showing_them() {
+        let argument = ProgramArgument::new(String::from("<token>"), String::from("token")).password().default(String::from("secret"));
+        let (answer, written) = ask(argument, "hunter2\n");
+        assert_eq!(answer.as_deref(), Some("hunter2"));
+        assert_eq!(written, "token: \n");
+    }
+
+    #[test]
+    fn gives_up_at_the_end_of_the_input() {
+        let argument = ProgramArgument::new(String::from("<size>"), String::from("size"));
+        assert_eq!(ask(argument, "").0, None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use super::error::ParseError;
+
+/// Replaces every `@path` argument with the arguments in that file, as set up by
+/// `Program::response_files`. Files can include other files; a relative path in a file is
+/// relative to that file's directory. Nothing after `--` is expanded.
+pub fn expand(args: &[String]) -> Result<Vec<String>, ParseError> {
+    let mut expander = Expander { args: Vec::new(), files: Vec::new(), positional: false };
+    for arg in args {
+        expander.arg(arg, Path::new(""), None)?;
+    }
+    Ok(expander.args)
+}
+
+struct Expander {
+    args: Vec<String>,
+    /// The files being expanded, outermost first, to catch a file that includes itself.
+    files: Vec<PathBuf>,
+    /// Set after `--`.
+    positional: bool
+}
+
+/// A file and line an argument was read from.
+type Origin<'a> = Option<(&'a Path, usize)>;
+
+impl Expander {
+    fn arg(&mut self, arg: &str, dir: &Path, origin: Origin) -> Result<(), ParseError> {
+        if !self.positional {
+            if arg == "--" {
+                self.positional = true;
+            } else if let Some(file) = arg.strip_prefix('@').filter(|file| !file.is_empty()) {
+                return self.file(&dir.join(file), origin);
+            }
+        }
+        self.args.push(String::from(arg));
+        Ok(())
+    }
+
+    /// Expands the arguments in `path`. Errors about the file itself point at the line that
+    /// included it, if there is one.
+    fn file(&mut self, path: &Path, origin: Origin) -> Result<(), ParseError> {
+        let error = |reason: String| match origin {
+            Some((file, line)) => ParseError::InvalidResponseFile { path: file.to_path_buf(), line, reason },
+            None => ParseError::InvalidResponseFile { path: path.to_path_buf(), line: 0, reason }
+        };
+        let text = std::fs::read_to_string(path).map_err(|io| match origin {
+            Some(_) => error(format!("cannot read {}: {}", path.display(), io)),
+            None => error(io.to_string())
+        })?;
+        let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.files.contains(&canonical) {
+            return Err(error(format!("{} includes itself", path.display())));
+        }
+
+        let words = split(&text).map_err(|(line, reason)| ParseError::InvalidResponseFile {
+            path: path.to_path_buf(),
+            line,
+            reason
+        })?;
+        self.files.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for Word { text, line, quoted } in words {
+            if quoted && !self.positional {
+                self.args.push(text);
+            } else {
+                self.arg(&text, dir, Some((path, line)))?;
+            }
+        }
+        self.files.pop();
+        Ok(())
+    }
+}
+
+/// A word of a response file.
+struct Word {
+    text: String,
+    /// The line the word starts on.
+    line: usize,
+    /// Whether any of the word was quoted, which keeps an `@` at its start from naming a file.
+    quoted: bool
+}
+
+/// Splits `text` into words at whitespace, keeping what is inside single or double quotes
+/// together. A backslash escapes a quote or another backslash and is kept before anything else,
+/// so Windows paths need no escaping.
+fn split(text: &str) -> Result<Vec<Word>, (usize, String)> {
+    let mut words = Vec::new();
+    let mut word: Option<Word> = None;
+    let new = |line| Word { text: String::new(), line, quoted: false };
+    let mut quote: Option<(char, usize)> = None;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('"' | '\'' | '\\')) => {
+                let escaped = chars.next().unwrap_or(c);
+                word.get_or_insert_with(|| new(line)).text.push(escaped);
+            }
+            '"' | '\'' if quote.is_none() => {
+                quote = Some((c, line));
+                word.get_or_insert_with(|| new(line)).quoted = true;
+            }
+            c if quote.is_some_and(|(open, _)| open == c) => quote = None,
+            c if c.is_whitespace() && quote.is_none() => words.extend(word.take()),
+            c => word.get_or_insert_with(|| new(line)).text.push(c)
+        }
+        if c == '\n' {
+            line += 1;
+        }
+    }
+    if let Some((open, at)) = quote {
+        return Err((at, format!("unterminated {} quote", open)));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::oop::testing::TempDir;
+
+    fn words(text: &str) -> Vec<String> {
+        split(text).unwrap().into_iter().map(|word| word.text).collect()
+    }
+
+    #[test]
+    fn splits_at_whitespace_outside_quotes() {
+        assert_eq!(words("--runs 3\n  -o 'out file.txt'\t\"a \\\"b\\\"\""), vec!["--runs", "3", "-o", "out file.txt", "a \"b\""]);
+        assert_eq!(words("C:\\dir\\file ''"), vec!["C:\\dir\\file", ""]);
+        assert_eq!(split("a\n'b\nc").err(), Some((2, String::from("unterminated ' quote"))));
+    }
+
+    #[test]
+    fn expands_files_recursively() {
+        let dir = TempDir::new("response");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("outer.txt"), "--runs 3\n@nested/inner.txt\n'@literal'").unwrap();
+        std::fs::write(dir.join("nested/inner.txt"), "-v \"two words\"").unwrap();
+        std::fs::write(dir.join("nested/loop.txt"), "-v\n@../cycle.txt").unwrap();
+        std::fs::write(dir.join("cycle.txt"), "@nested/loop.txt").unwrap();
+        let at = |name: &str| format!("@{}", dir.join(name).display());
+
+        let args = vec![String::from("bench"), at("outer.txt"), String::from("--"), at("outer.txt")];
+        assert_eq!(expand(&args).unwrap(), vec!["bench", "--runs", "3", "-v", "two words", "@literal", "--", &at("outer.txt")]);
+
+        assert_eq!(
+            expand(&[at("cycle.txt")]).unwrap_err(),
+            ParseError::InvalidResponseFile {
+                path: dir.join("nested/loop.txt"),
+                line: 2,
+                reason: format!("{} includes itself", dir.join("nested/../cycle.txt").display())
+            }
+        );
+        match expand(&[at("missing.txt")]).unwrap_err() {
+            ParseError::InvalidResponseFile { path, line, .. } => assert_eq!((path, line), (dir.join("missing.txt"), 0)),
+            error => panic!("unexpected error {:?}", error)
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::str::FromStr;
+
+/// When help and errors are styled with ANSI escape codes, as set with `Program::color` or given
+/// with `--color`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal, and `NO_COLOR` is not set to anything.
+    #[default]
+    Auto,
+    Always,
+    Never
+}
+
+impl ColorChoice {
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never"
+        }
+    }
+
+    /// The style for writing to a stream, which `terminal` says is a terminal or not.
+    pub fn style(self, terminal: bool) -> Style {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Style::new(self.enabled(terminal, no_color))
+    }
+
+    /// An explicit choice wins over `NO_COLOR`, which only turns off the automatic one.
+    fn enabled(self, terminal: bool, no_color: bool) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && !no_color,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ColorChoice, String> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("Unknown color choice '{}', expected one of {}", name, ColorChoice::NAMES.join(", ")))
+        }
+    }
+}
+
+/// How to style the parts of help and errors. When it is off, text is left as it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    enabled: bool
+}
+
+impl Style {
+    pub fn new(enabled: bool) -> Self {
+        Style { enabled }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Section headings of help, such as `Options:`, in bold.
+    pub fn heading(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    /// Command names, in cyan.
+    pub fn command(&self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    /// The `error:` label, in bold red.
+    pub fn error(&self, text: &str) -> String {
+        self.paint("1;31", text)
+    }
+
+    /// What an error suggests instead, in bold yellow.
+    pub fn suggestion(&self, text: &str) -> String {
+        self.paint("1;33", text)
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            String::from(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paints_only_when_enabled() {
+        assert_eq!(Style::new(true).error("error:"), "\x1b[1;31merror:\x1b[0m");
+        assert_eq!(Style::new(true).heading(""), "");
+        assert_eq!(Style::new(false).command("push"), "push");
+    }
+
+    #[test]
+    fn explicit_choices_win_over_no_color() {
+        assert!(ColorChoice::Auto.enabled(true, false));
+        assert!(!ColorChoice::Auto.enabled(true, true));
+        assert!(!ColorChoice::Auto.enabled(false, false));
+        assert!(ColorChoice::Always.enabled(false, true));
+        assert!(!ColorChoice::Never.enabled(true, false));
+        assert_eq!("always".parse::<ColorChoice>(), Ok(ColorChoice::Always));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+}
@@ -0,0 +1,29 @@
+use std::path::{Path, PathBuf};
+
+/// A directory for the files of one test, removed when it is dropped, so it is cleaned up even
+/// when an assertion fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `how_to_rust_<name>_<process id>` in the system's temp directory. `name` must be
+    /// unique among the tests, as they run at the same time.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("how_to_rust_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
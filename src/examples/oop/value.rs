@@ -0,0 +1,261 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use super::config::ValueSource;
+use super::{ProgramArgument, ProgramOption};
+
+/// Turns the raw text of an argument or option into a typed value.
+pub trait ValueParser {
+    /// What the parser expects, as shown in error messages, e.g. `"an integer"`.
+    fn expected(&self) -> String;
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>>;
+
+    /// The only values that are accepted, if the parser has a fixed set of them.
+    fn choices(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    /// The name of a built-in parser, such as `"integer"`, which is how a JSON definition of the
+    /// program refers to it. Parsers defined outside this module have none.
+    fn kind(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A custom check run on the raw text after it parsed. The error is shown to the user.
+pub type Validator = fn(&str) -> Result<(), String>;
+
+/// Keeps the text as a `String`. This is the parser arguments and options start with.
+pub struct StringParser;
+
+/// Parses an `i64`.
+pub struct IntegerParser;
+
+/// Parses an `f64`.
+pub struct FloatParser;
+
+/// Parses a `bool` from `true`/`false`, `yes`/`no`, `on`/`off` or `1`/`0`.
+pub struct BoolParser;
+
+/// Parses a `PathBuf`.
+pub struct PathParser;
+
+/// Accepts one of a fixed list of strings and keeps it as a `String`.
+pub struct EnumParser(pub Vec<String>);
+
+impl ValueParser for StringParser {
+    fn expected(&self) -> String {
+        String::from("a string")
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        Some(Rc::new(String::from(raw)))
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("string")
+    }
+}
+
+impl ValueParser for IntegerParser {
+    fn expected(&self) -> String {
+        String::from("an integer")
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        raw.parse::<i64>().ok().map(|value| Rc::new(value) as Rc<dyn Any>)
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("integer")
+    }
+}
+
+impl ValueParser for FloatParser {
+    fn expected(&self) -> String {
+        String::from("a number")
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        raw.parse::<f64>().ok().map(|value| Rc::new(value) as Rc<dyn Any>)
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("float")
+    }
+}
+
+impl ValueParser for BoolParser {
+    fn expected(&self) -> String {
+        String::from("a boolean")
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        match raw.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Some(Rc::new(true)),
+            "false" | "no" | "off" | "0" => Some(Rc::new(false)),
+            _ => None
+        }
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("bool")
+    }
+}
+
+impl ValueParser for PathParser {
+    fn expected(&self) -> String {
+        String::from("a path")
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        if raw.is_empty() {
+            return None;
+        }
+        Some(Rc::new(PathBuf::from(raw)))
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("path")
+    }
+}
+
+impl ValueParser for EnumParser {
+    fn expected(&self) -> String {
+        format!("one of {}", self.0.join(", "))
+    }
+
+    fn parse(&self, raw: &str) -> Option<Rc<dyn Any>> {
+        if self.0.iter().any(|choice| choice == raw) {
+            return Some(Rc::new(String::from(raw)));
+        }
+        None
+    }
+
+    fn choices(&self) -> Option<Vec<String>> {
+        Some(self.0.clone())
+    }
+
+    fn kind(&self) -> Option<&'static str> {
+        Some("enum")
+    }
+}
+
+impl fmt::Debug for Matches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.values.keys().collect();
+        names.sort();
+        f.debug_struct("Matches").field("names", &names).finish()
+    }
+}
+
+/// The typed values of a parsed command, looked up by argument name (without the `<>` or `[]`)
+/// or by option name (its long flag, or its short flag if it has no long one).
+#[derive(Clone, Default)]
+pub struct Matches {
+    values: HashMap<String, Vec<Rc<dyn Any>>>,
+    sources: HashMap<String, ValueSource>
+}
+
+impl Matches {
+    pub fn new(arguments: &[ProgramArgument], options: &[ProgramOption]) -> Self {
+        let mut values = HashMap::new();
+        let mut sources = HashMap::new();
+        for argument in arguments {
+            if !argument.parsed.is_empty() {
+                values.insert(argument.key(), argument.parsed.clone());
+            }
+        }
+        for option in options {
+            if !option.argument.parsed.is_empty() {
+                values.insert(option.name(), option.argument.parsed.clone());
+            }
+            sources.insert(option.name(), option.source.clone());
+        }
+        Self { values, sources }
+    }
+
+    /// The value of `name`, or `None` if it has no value or its value is not a `T`. For a
+    /// variadic argument this is the first of its values.
+    pub fn get<T: 'static>(&self, name: &str) -> Option<&T> {
+        self.values.get(name)?.first()?.downcast_ref::<T>()
+    }
+
+    /// Every value of `name` that is a `T`, in the order they were given.
+    #[allow(dead_code)]
+    pub fn get_many<T: 'static>(&self, name: &str) -> Vec<&T> {
+        self.values
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.downcast_ref::<T>())
+            .collect()
+    }
+
+    /// Where the value of the option `name` came from.
+    #[allow(dead_code)]
+    pub fn source(&self, name: &str) -> Option<&ValueSource> {
+        self.sources.get(name)
+    }
+
+    /// Sets the value of `name`, replacing any it had. The value no longer came from where the
+    /// old one did, so `source` has nothing for it afterwards.
+    #[allow(dead_code)]
+    pub fn insert<T: 'static>(&mut self, name: &str, value: T) {
+        self.values.insert(String::from(name), vec![Rc::new(value)]);
+        self.sources.remove(name);
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Whether a boolean flag was turned on. Flags that were not given are off.
+    #[allow(dead_code)]
+    pub fn flag(&self, name: &str) -> bool {
+        self.get::<bool>(name).copied().unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse<T: Clone + 'static>(parser: &dyn ValueParser, raw: &str) -> Option<T> {
+        parser.parse(raw)?.downcast_ref::<T>().cloned()
+    }
+
+    #[test]
+    fn built_in_parsers() {
+        assert_eq!(parse::<i64>(&IntegerParser, "-42"), Some(-42));
+        assert_eq!(parse::<i64>(&IntegerParser, "4.2"), None);
+        assert_eq!(parse::<f64>(&FloatParser, "4.5"), Some(4.5));
+        assert_eq!(parse::<bool>(&BoolParser, "Yes"), Some(true));
+        assert_eq!(parse::<bool>(&BoolParser, "0"), Some(false));
+        assert_eq!(parse::<bool>(&BoolParser, "maybe"), None);
+        assert_eq!(parse::<PathBuf>(&PathParser, "a/b"), Some(PathBuf::from("a/b")));
+        assert_eq!(parse::<String>(&StringParser, "text"), Some(String::from("text")));
+    }
+
+    #[test]
+    fn enum_parser_only_accepts_choices() {
+        let parser = EnumParser(vec![String::from("bash"), String::from("zsh")]);
+        assert_eq!(parse::<String>(&parser, "zsh"), Some(String::from("zsh")));
+        assert_eq!(parse::<String>(&parser, "fish"), None);
+        assert_eq!(parser.expected(), "one of bash, zsh");
+    }
+
+    #[test]
+    fn inserting_a_value_forgets_its_source() {
+        let mut matches = Matches::default();
+        matches.sources.insert(String::from("size"), ValueSource::CommandLine);
+        matches.insert("size", 4_i64);
+        assert_eq!(matches.get::<i64>("size"), Some(&4));
+        assert_eq!(matches.source("size"), None);
+    }
+}
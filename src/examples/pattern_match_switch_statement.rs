@@ -1,4 +1,4 @@
-use crate::examples::{function_as_arg, get_home_dir, import_function, console_log, oop};
+use crate::examples::{function_as_arg, get_home_dir, import_function, console_log, multi_line_string, oop};
 
 /// This use of the match method is called "pattern matching" in Rustlang. It is similar to a
 /// switch statement.
@@ -8,7 +8,8 @@ pub fn run(command: &String) {
         "function_as_arg" => function_as_arg::run(),
         "get_home_dir" => get_home_dir::run(),
         "import_function" => import_function::run(),
+        "multi_line_string" => multi_line_string::run(),
         "oop" => oop::run(),
         _ => println!("Invalid command")
     }
-}
\ No newline at end of file
+}
@@ -1,26 +1,146 @@
 mod examples;
 
-fn main() {
-    // Create a collection of command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    // println!("{:?}", args);
-    if args.len() > 1 {
-        let command = &args[1];
-        examples::pattern_match_switch_statement::run(command);
-    } else {
-        println!(
-            "\
-Usage: how_to_rust [command]
-  Each command runs an example file named after the command.
-  (This CLI uses the `pattern_match_switch_statement` example.)
+use examples::oop::completion::Shell;
+use examples::oop::value::EnumParser;
+use examples::oop::Program;
+use examples::{console_log, function_as_arg, get_home_dir, import_function, multi_line_string, oop, pattern_match_switch_statement};
+
+/// The examples the CLI can run, as `(command, description)`. Each command runs the example file
+/// named after it. The `docs` command lists them, so this is the one place to describe them.
+const EXAMPLES: [(&str, &str); 7] = [
+    ("console_log", "Log to the console"),
+    ("function_as_arg", "Accept a function as an argument"),
+    ("get_home_dir", "Get the home directory"),
+    ("import_function", "Import a module function"),
+    ("multi_line_string", "Print a multiline string"),
+    ("oop", "Create a class"),
+    ("pattern_match_switch_statement", "Pattern match (e.g. switch statement) to run another example")
+];
+
+/// The description of one of `EXAMPLES`.
+fn describe(name: &str) -> &'static str {
+    EXAMPLES
+        .iter()
+        .find(|(example, _)| *example == name)
+        .map(|(_, description)| *description)
+        .unwrap_or_else(|| panic!("'{}' is not in EXAMPLES", name))
+}
+
+/// The generated part of the README: a link to each example, then the command reference.
+fn docs() -> String {
+    let mut out = String::from("- [Accept command line arguments](./src/main.rs)\n");
+    for (name, description) in EXAMPLES {
+        out.push_str(&format!("- [{}](src/examples/{}.rs)\n", description, name));
+    }
+    out.push('\n');
+    out.push_str(&program().markdown());
+    out
+}
 
-Commands:
-  console_log
-  function_as_arg
-  get_home_dir
-  import_function
-  oop
-"
-        );
+/// The CLI is a `Program` tree (see the `oop` example) with one command per example file. The
+/// usage text and the README's reference are generated from this tree, so adding a command here
+/// is all it takes to list it.
+fn program() -> Program {
+    program! {
+        "how_to_rust" {
+            version env!("CARGO_PKG_VERSION");
+            description "Each command runs an example file named after the command.";
+            external_subcommands;
+            response_files;
+            color_flag;
+            command "completions" {
+                description "Print a shell completion script for this CLI";
+                argument "<shell>" "shell to complete in"
+                    .parser(EnumParser(Shell::NAMES.iter().map(|name| String::from(*name)).collect()));
+                action |context| {
+                    let shell: Shell = context.matches().get::<String>("shell").unwrap().parse()?;
+                    print!("{}", program().completions(shell));
+                    Ok(())
+                };
+            }
+            command "console_log" {
+                description describe("console_log");
+                action |_| {
+                    console_log::main();
+                    Ok(())
+                };
+            }
+            command "docs" {
+                description "Print the example list and command reference for the README";
+                action |_| {
+                    print!("{}", docs());
+                    Ok(())
+                };
+            }
+            command "function_as_arg" {
+                description describe("function_as_arg");
+                action |_| {
+                    function_as_arg::run();
+                    Ok(())
+                };
+            }
+            command "get_home_dir" {
+                description describe("get_home_dir");
+                action |_| {
+                    get_home_dir::run();
+                    Ok(())
+                };
+            }
+            command "import_function" {
+                description describe("import_function");
+                action |_| {
+                    import_function::run();
+                    Ok(())
+                };
+            }
+            command "json" {
+                description "Print this CLI's definition as JSON, for tools that are not written in Rust";
+                action |_| {
+                    println!("{}", program().to_json());
+                    Ok(())
+                };
+            }
+            command "man" {
+                description "Write man pages for this CLI to a directory";
+                argument "<directory>" "directory to write the pages to";
+                action |context| {
+                    let directory = context.matches().get::<String>("directory").unwrap();
+                    let directory = std::path::Path::new(directory);
+                    std::fs::create_dir_all(directory)
+                        .map_err(|error| format!("{}: {}", directory.display(), error))?;
+                    for (name, page) in program().man_pages() {
+                        std::fs::write(directory.join(&name), page).map_err(|error| format!("{}: {}", name, error))?;
+                    }
+                    Ok(())
+                };
+            }
+            command "multi_line_string" {
+                description describe("multi_line_string");
+                action |_| {
+                    multi_line_string::run();
+                    Ok(())
+                };
+            }
+            command "oop" {
+                description describe("oop");
+                action |_| {
+                    oop::run();
+                    Ok(())
+                };
+            }
+            command "pattern_match_switch_statement" {
+                description describe("pattern_match_switch_statement");
+                argument "<example>" "name of the example to run";
+                action |context| {
+                    pattern_match_switch_statement::run(context.matches().get::<String>("example").unwrap());
+                    Ok(())
+                };
+            }
+        }
     }
 }
+
+fn main() {
+    // Parse the command line arguments and run the matching example
+    std::process::exit(program().run());
+}